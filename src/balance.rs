@@ -0,0 +1,1084 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::building::{BuildingType, ProductionType};
+use crate::enemy::{EnemyArchetype, EnemySize};
+use crate::resource::Cost;
+use crate::resource::ResourceType::{self, Coal, Copper, Iron, Oil, Scrap, Stone};
+use crate::outcome::VictoryConditions;
+use crate::weapon::{AmmoType, WeaponType};
+
+/// Loaded at startup if present; any value it leaves out keeps its default.
+pub const BALANCE_PATH: &str = "balance.ron";
+
+/// Every tunable number in the game. The `Default` impl holds the built-in
+/// values, so a balance file only needs to list what it changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Balance {
+    /// Seconds before the first wave.
+    pub grace_period: f32,
+    pub spawn_distance_min: f32,
+    pub spawn_distance_max: f32,
+    pub truck: TruckStats,
+    pub turret: TurretStats,
+    pub production: ProductionTable,
+    pub weapons: WeaponTable,
+    pub ammo: AmmoTable,
+    pub buildings: BuildingTable,
+    pub enemies: EnemyTable,
+    pub boss: BossTable,
+    pub ore: OreTable,
+    pub waves: WaveTable,
+    pub victory: VictoryConditions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TruckStats {
+    pub speed: f32,
+    pub max_cargo: u32,
+    pub max_health: u32,
+    /// Taken off every enemy hit, though a hit always does at least 1.
+    pub armor: u32,
+    /// Share of the overlap between two touching trucks (or a truck and a
+    /// building) that is pushed apart each tick, from 0 to 1.
+    pub separation: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TurretStats {
+    pub weapon_range: f32,
+    /// Seconds between shots.
+    pub fire_rate: f32,
+    pub bullet_damage: u32,
+    /// Bullet speed in pixels per second.
+    pub projectile_speed: f32,
+    /// Bullets the turret can hold; it refills a box (`ammo.bullets.per_box`) at a time.
+    pub max_bullets: u32,
+    /// Turrets draw bullet boxes from any factory this close.
+    pub supply_range: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProductionStats {
+    pub cost: Cost,
+    /// Seconds to produce one item.
+    pub time: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProductionTable {
+    pub truck: ProductionStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponStats {
+    /// Damage to each enemy hit.
+    pub damage: u32,
+    pub range: f32,
+    /// Seconds between shots.
+    pub fire_rate: f32,
+    /// Pixels per second; cone weapons hit instantly and ignore it, so it
+    /// only has to be positive when `cone_angle` is 0.
+    pub projectile_speed: f32,
+    /// Shots burst where they land, hitting every enemy this close; 0 hits one enemy.
+    pub splash_radius: f32,
+    /// Share of the damage lost toward the edge of the burst, from 0 to 1.
+    pub splash_falloff: f32,
+    /// Width in degrees of the cone hit with every shot; 0 fires projectiles instead.
+    pub cone_angle: f32,
+    pub ammo: AmmoType,
+    /// Rounds a truck carries.
+    pub max_ammo: u32,
+    pub recipe: ProductionStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeaponTable {
+    pub machine_gun: WeaponStats,
+    pub cannon: WeaponStats,
+    pub rifle: WeaponStats,
+    pub flamethrower: WeaponStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmmoStats {
+    /// Rounds in one box.
+    pub per_box: u32,
+    pub recipe: ProductionStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AmmoTable {
+    pub bullets: AmmoStats,
+    pub shells: AmmoStats,
+    pub fuel: AmmoStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildingStats {
+    /// Half the width of the square footprint.
+    pub size: f32,
+    pub max_health: u32,
+    pub cost: Cost,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildingTable {
+    pub beacon: BuildingStats,
+    pub garage: BuildingStats,
+    pub factory: BuildingStats,
+    pub turret: BuildingStats,
+    /// One wall segment; a dragged wall is a line of these.
+    pub wall: BuildingStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnemyStats {
+    pub radius: f32,
+    pub health: u32,
+    pub speed: f32,
+    /// Damage dealt to each building in reach, every tick.
+    pub damage: u32,
+    /// Blast set off when the enemy dies, if any.
+    #[serde(default)]
+    pub explosion: Option<BlastStats>,
+    /// Taken off the damage of every hit; unlike truck armor it can soak
+    /// a hit entirely.
+    #[serde(default)]
+    pub armor: u32,
+    /// Special trick, if any.
+    #[serde(default)]
+    pub ability: Option<EnemyAbility>,
+}
+
+/// What sets an archetype apart from a plain enemy.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum EnemyAbility {
+    /// Goes after unarmed trucks it can see from `sight` away, ignoring
+    /// armed trucks and buildings.
+    HuntMiners { sight: f32 },
+    /// Stops `range` away from its target and lobs acid at it.
+    Spit { range: f32, damage: u32, fire_rate: f32, projectile_speed: f32, splash_radius: f32 },
+    /// Travels underground, out of reach and under walls, and surfaces
+    /// `surface_distance` from the beacon.
+    Burrow { surface_distance: f32 },
+    /// Breaks into `count` small enemies when killed.
+    Split { count: u32 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlastStats {
+    pub radius: f32,
+    /// Damage at the center.
+    pub damage: u32,
+    /// Share of the damage lost toward the edge, from 0 to 1.
+    pub falloff: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnemyTable {
+    pub small: EnemyStats,
+    pub medium: EnemyStats,
+    pub large: EnemyStats,
+    pub scout: EnemyStats,
+    pub tank: EnemyStats,
+    pub spitter: EnemyStats,
+    pub burrower: EnemyStats,
+    pub splitter: EnemyStats,
+    /// Enemies leave the beacon to go after trucks and buildings this close.
+    pub aggro_range: f32,
+    /// Seconds a new enemy mills about near its spawn point before it
+    /// sets off for the beacon.
+    pub wander_time: f32,
+    /// How far from its spawn point a wandering enemy strays.
+    pub wander_radius: f32,
+    /// Share of its health, from 0 to 1, below which an enemy under fire
+    /// runs away. Each enemy only runs once.
+    pub flee_health: f32,
+    /// Seconds a fleeing enemy runs before it turns back to its pack.
+    pub flee_time: f32,
+    /// Enemies this close to each other count as one pack, both for
+    /// sizing up the trucks shooting at them and for regrouping.
+    pub pack_radius: f32,
+    /// Longest an enemy spends catching up with its pack after fleeing.
+    pub regroup_time: f32,
+    /// Seconds an enemy spends edging along a wall before it stops to
+    /// break through instead.
+    pub wall_patience: f32,
+    /// Share of the overlap between two touching enemies that is pushed
+    /// apart each tick, from 0 (they stack freely) to 1.
+    pub separation: f32,
+    /// Scrap left behind by a killed enemy, by size; archetypes drop
+    /// the amount for the size they count as.
+    pub scrap: ScrapDrops,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScrapDrops {
+    pub small: u32,
+    pub medium: u32,
+    pub large: u32,
+}
+
+impl ScrapDrops {
+    pub fn for_size(&self, size: EnemySize) -> u32 {
+        match size {
+            EnemySize::Small => self.small,
+            EnemySize::Medium => self.medium,
+            EnemySize::Large => self.large,
+        }
+    }
+}
+
+/// A single huge enemy that comes with every few waves and fights in
+/// phases as it loses health: shielded, then summoning smalls, then
+/// charging the nearest factory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BossTable {
+    pub stats: EnemyStats,
+    /// A boss joins every wave whose number is a multiple of this; 0
+    /// turns bosses off.
+    pub every_waves: u32,
+    /// Extra armor while the shield is up.
+    pub shield_armor: u32,
+    /// Share of its health, from 0 to 1, at which the shield drops and
+    /// summoning starts.
+    pub summon_below: f32,
+    /// Share of its health at which it stops summoning and charges.
+    pub charge_below: f32,
+    /// Seconds between summons.
+    pub summon_interval: f32,
+    /// Small enemies called up by each summon.
+    pub summon_count: u32,
+    /// Speed while charging.
+    pub charge_speed: f32,
+    /// Dropped on the ground when it dies.
+    pub loot: Cost,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OreTable {
+    /// Ore in each of the two patches next to the beacon.
+    pub starting_amount: u32,
+    /// Extra patches scattered around the map when it is created.
+    pub initial_fields: u32,
+    pub field_distance_min: f32,
+    pub field_distance_max: f32,
+    /// Ore in a generated patch before its distance bonus.
+    pub richness_min: u32,
+    pub richness_max: u32,
+    /// Seconds between new patches appearing; 0 turns it off.
+    pub discovery_interval: f32,
+    /// Each discovered patch appears this much further out than the last.
+    pub discovery_distance_step: f32,
+    /// Relative chance of each ore for a generated patch. Any ore with a
+    /// weight that the starting patches lack is given one of the initial
+    /// fields first, so it can be found from the start.
+    pub weights: BTreeMap<ResourceType, u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveTable {
+    /// Seconds between the last spawn of one wave and the start of the next.
+    pub break_time: f32,
+    /// Seconds between individual spawns within a wave.
+    pub spawn_spacing: f32,
+    /// Hand-authored waves, played in order.
+    pub waves: Vec<WaveDefinition>,
+    /// How waves past the authored list grow from the last one.
+    pub escalation: WaveEscalation,
+    /// How often each archetype turns up in place of a plain enemy.
+    pub archetypes: ArchetypeSpawns,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchetypeSpawns {
+    pub scout: ArchetypeSpawn,
+    pub tank: ArchetypeSpawn,
+    pub spitter: ArchetypeSpawn,
+    pub burrower: ArchetypeSpawn,
+    pub splitter: ArchetypeSpawn,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchetypeSpawn {
+    /// Chance, from 0 to 1, that any one wave enemy is of this archetype.
+    pub weight: f32,
+    /// First wave it can appear in.
+    pub from_wave: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveDefinition {
+    pub groups: Vec<SpawnGroup>,
+}
+
+/// A cluster of enemies arriving from one direction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnGroup {
+    pub small: u32,
+    pub medium: u32,
+    pub large: u32,
+    /// Compass bearing in degrees (0 = east, 90 = south); random if omitted.
+    pub direction: Option<f32>,
+    /// Degrees either side of `direction` the group is scattered over.
+    pub spread: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveEscalation {
+    /// Enemy counts are multiplied by this for every wave past the list.
+    pub count_growth: f32,
+    /// Enemy health is multiplied by this for every wave after the first.
+    pub health_growth: f32,
+    /// Past the list, add another random-direction group this often.
+    pub extra_group_every: u32,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            grace_period: 300.0,
+            spawn_distance_min: 1000.0,
+            spawn_distance_max: 1500.0,
+            truck: TruckStats::default(),
+            turret: TurretStats::default(),
+            production: ProductionTable::default(),
+            weapons: WeaponTable::default(),
+            ammo: AmmoTable::default(),
+            buildings: BuildingTable::default(),
+            enemies: EnemyTable::default(),
+            boss: BossTable::default(),
+            ore: OreTable::default(),
+            waves: WaveTable::default(),
+            victory: VictoryConditions::default(),
+        }
+    }
+}
+
+impl Default for TruckStats {
+    fn default() -> Self {
+        Self {
+            speed: 100.0,
+            max_cargo: 64,
+            max_health: 600,
+            armor: 2,
+            separation: 0.5,
+        }
+    }
+}
+
+impl Default for ProductionTable {
+    fn default() -> Self {
+        Self {
+            truck: ProductionStats { cost: Cost::from([(Iron, 20), (Coal, 10)]), time: 5.0 },
+        }
+    }
+}
+
+impl Default for WeaponTable {
+    fn default() -> Self {
+        Self {
+            machine_gun: WeaponStats {
+                damage: 1,
+                range: 150.0,
+                fire_rate: 0.5,
+                projectile_speed: 500.0,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                cone_angle: 0.0,
+                ammo: AmmoType::Bullets,
+                max_ammo: 400,
+                recipe: ProductionStats { cost: Cost::from([(Iron, 30), (Coal, 5)]), time: 8.0 },
+            },
+            cannon: WeaponStats {
+                damage: 12,
+                range: 180.0,
+                fire_rate: 2.5,
+                projectile_speed: 250.0,
+                splash_radius: 45.0,
+                splash_falloff: 0.7,
+                cone_angle: 0.0,
+                ammo: AmmoType::Shells,
+                max_ammo: 40,
+                recipe: ProductionStats { cost: Cost::from([(Iron, 80), (Coal, 30), (Scrap, 20)]), time: 12.0 },
+            },
+            rifle: WeaponStats {
+                damage: 12,
+                range: 300.0,
+                fire_rate: 1.5,
+                projectile_speed: 900.0,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                cone_angle: 0.0,
+                ammo: AmmoType::Bullets,
+                max_ammo: 200,
+                recipe: ProductionStats { cost: Cost::from([(Iron, 50), (Coal, 15), (Copper, 10), (Scrap, 10)]), time: 10.0 },
+            },
+            flamethrower: WeaponStats {
+                damage: 1,
+                range: 90.0,
+                fire_rate: 0.1,
+                projectile_speed: 0.0,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                cone_angle: 40.0,
+                ammo: AmmoType::Fuel,
+                max_ammo: 300,
+                recipe: ProductionStats { cost: Cost::from([(Iron, 40), (Coal, 20), (Oil, 10)]), time: 10.0 },
+            },
+        }
+    }
+}
+
+impl Default for AmmoTable {
+    fn default() -> Self {
+        Self {
+            bullets: AmmoStats { per_box: 100, recipe: ProductionStats { cost: Cost::from([(Iron, 5), (Coal, 10)]), time: 3.0 } },
+            shells: AmmoStats { per_box: 10, recipe: ProductionStats { cost: Cost::from([(Iron, 15), (Coal, 10), (Scrap, 2)]), time: 4.0 } },
+            fuel: AmmoStats { per_box: 100, recipe: ProductionStats { cost: Cost::from([(Coal, 5), (Oil, 10)]), time: 3.0 } },
+        }
+    }
+}
+
+impl Default for BuildingTable {
+    fn default() -> Self {
+        Self {
+            beacon: BuildingStats { size: 30.0, max_health: 1000, cost: Cost::default() },
+            garage: BuildingStats { size: 40.0, max_health: 300, cost: Cost::from([(Iron, 50), (Coal, 30)]) },
+            factory: BuildingStats { size: 50.0, max_health: 400, cost: Cost::from([(Iron, 100), (Coal, 50)]) },
+            turret: BuildingStats { size: 20.0, max_health: 250, cost: Cost::from([(Iron, 40), (Coal, 20), (Copper, 10)]) },
+            wall: BuildingStats { size: 10.0, max_health: 600, cost: Cost::from([(Stone, 5)]) },
+        }
+    }
+}
+
+impl Default for TurretStats {
+    fn default() -> Self {
+        Self {
+            weapon_range: 200.0,
+            fire_rate: 0.4,
+            bullet_damage: 1,
+            projectile_speed: 600.0,
+            max_bullets: 300,
+            supply_range: 250.0,
+        }
+    }
+}
+
+impl Default for EnemyTable {
+    fn default() -> Self {
+        Self {
+            small: EnemyStats { radius: 10.0, health: 10, speed: 20.0, damage: 1, ..EnemyStats::default() },
+            medium: EnemyStats { radius: 25.0, health: 40, speed: 15.0, damage: 2, ..EnemyStats::default() },
+            large: EnemyStats {
+                radius: 50.0,
+                health: 100,
+                speed: 10.0,
+                damage: 5,
+                explosion: Some(BlastStats { radius: 90.0, damage: 120, falloff: 0.6 }),
+                ..EnemyStats::default()
+            },
+            scout: EnemyStats {
+                radius: 8.0,
+                health: 6,
+                speed: 45.0,
+                damage: 1,
+                ability: Some(EnemyAbility::HuntMiners { sight: 400.0 }),
+                ..EnemyStats::default()
+            },
+            tank: EnemyStats { radius: 35.0, health: 150, speed: 8.0, damage: 4, armor: 2, ..EnemyStats::default() },
+            spitter: EnemyStats {
+                radius: 15.0,
+                health: 25,
+                speed: 14.0,
+                damage: 1,
+                ability: Some(EnemyAbility::Spit {
+                    range: 200.0,
+                    damage: 6,
+                    fire_rate: 2.0,
+                    projectile_speed: 150.0,
+                    splash_radius: 20.0,
+                }),
+                ..EnemyStats::default()
+            },
+            burrower: EnemyStats {
+                radius: 18.0,
+                health: 30,
+                speed: 18.0,
+                damage: 3,
+                ability: Some(EnemyAbility::Burrow { surface_distance: 150.0 }),
+                ..EnemyStats::default()
+            },
+            splitter: EnemyStats {
+                radius: 30.0,
+                health: 60,
+                speed: 12.0,
+                damage: 2,
+                ability: Some(EnemyAbility::Split { count: 4 }),
+                ..EnemyStats::default()
+            },
+            aggro_range: 200.0,
+            wander_time: 5.0,
+            wander_radius: 80.0,
+            flee_health: 0.25,
+            flee_time: 4.0,
+            pack_radius: 150.0,
+            regroup_time: 6.0,
+            wall_patience: 4.0,
+            separation: 0.5,
+            scrap: ScrapDrops { small: 2, medium: 5, large: 12 },
+        }
+    }
+}
+
+impl Default for BossTable {
+    fn default() -> Self {
+        Self {
+            stats: EnemyStats { radius: 70.0, health: 1200, speed: 6.0, damage: 10, armor: 1, ..EnemyStats::default() },
+            every_waves: 5,
+            shield_armor: 8,
+            summon_below: 0.7,
+            charge_below: 0.3,
+            summon_interval: 6.0,
+            summon_count: 3,
+            charge_speed: 30.0,
+            loot: Cost::from([(Iron, 300), (Coal, 150), (Scrap, 60)]),
+        }
+    }
+}
+
+impl Default for OreTable {
+    fn default() -> Self {
+        Self {
+            starting_amount: 3000,
+            initial_fields: 4,
+            field_distance_min: 400.0,
+            field_distance_max: 900.0,
+            richness_min: 1000,
+            richness_max: 4000,
+            discovery_interval: 240.0,
+            discovery_distance_step: 150.0,
+            weights: BTreeMap::from([(Iron, 3), (Coal, 3), (Copper, 2), (Oil, 2), (Stone, 2)]),
+        }
+    }
+}
+
+impl Default for WaveTable {
+    fn default() -> Self {
+        let group = |small, medium, large| SpawnGroup { small, medium, large, ..SpawnGroup::default() };
+        Self {
+            break_time: 60.0,
+            spawn_spacing: 0.5,
+            waves: vec![
+                WaveDefinition { groups: vec![group(4, 0, 0)] },
+                WaveDefinition { groups: vec![group(6, 1, 0)] },
+                WaveDefinition { groups: vec![group(5, 1, 0), group(4, 0, 0)] },
+                WaveDefinition { groups: vec![group(6, 2, 0), group(3, 1, 0)] },
+                WaveDefinition { groups: vec![group(8, 2, 1), group(6, 1, 0)] },
+            ],
+            escalation: WaveEscalation::default(),
+            archetypes: ArchetypeSpawns::default(),
+        }
+    }
+}
+
+impl Default for ArchetypeSpawns {
+    fn default() -> Self {
+        let spawn = |weight, from_wave| ArchetypeSpawn { weight, from_wave };
+        Self {
+            scout: spawn(0.12, 2),
+            tank: spawn(0.06, 4),
+            spitter: spawn(0.1, 3),
+            burrower: spawn(0.05, 5),
+            splitter: spawn(0.06, 5),
+        }
+    }
+}
+
+impl Default for SpawnGroup {
+    fn default() -> Self {
+        Self {
+            small: 0,
+            medium: 0,
+            large: 0,
+            direction: None,
+            spread: 15.0,
+        }
+    }
+}
+
+impl Default for WaveEscalation {
+    fn default() -> Self {
+        Self {
+            count_growth: 1.2,
+            health_growth: 1.05,
+            extra_group_every: 3,
+        }
+    }
+}
+
+impl SpawnGroup {
+    pub fn total(&self) -> u32 {
+        self.small + self.medium + self.large
+    }
+}
+
+#[derive(Debug)]
+pub enum BalanceError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    /// One message per invalid value.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceError::Io(err) => write!(f, "could not read {}: {}", BALANCE_PATH, err),
+            BalanceError::Parse(err) => write!(f, "{}:{}", BALANCE_PATH, err),
+            BalanceError::Invalid(problems) => {
+                write!(f, "{} has invalid values:", BALANCE_PATH)?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BalanceError {}
+
+impl Balance {
+    /// Load the balance file, falling back to the built-in values if it
+    /// doesn't exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self, BalanceError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(BalanceError::Io(err)),
+        };
+        let balance: Self = ron::from_str(&text).map_err(BalanceError::Parse)?;
+        balance.validate()?;
+        Ok(balance)
+    }
+
+    /// Check every value for something the game can actually run with.
+    pub fn validate(&self) -> Result<(), BalanceError> {
+        let mut problems = Vec::new();
+        let mut positive = |name: &str, value: f32| {
+            if !(value > 0.0 && value.is_finite()) {
+                problems.push(format!("{} must be greater than 0 (got {})", name, value));
+            }
+        };
+
+        positive("spawn_distance_min", self.spawn_distance_min);
+        positive("spawn_distance_max", self.spawn_distance_max);
+        positive("truck.speed", self.truck.speed);
+        positive("turret.weapon_range", self.turret.weapon_range);
+        positive("turret.fire_rate", self.turret.fire_rate);
+        positive("turret.projectile_speed", self.turret.projectile_speed);
+        positive("turret.supply_range", self.turret.supply_range);
+        positive("enemies.aggro_range", self.enemies.aggro_range);
+        positive("enemies.wander_radius", self.enemies.wander_radius);
+        positive("enemies.pack_radius", self.enemies.pack_radius);
+        positive("waves.escalation.count_growth", self.waves.escalation.count_growth);
+        positive("waves.escalation.health_growth", self.waves.escalation.health_growth);
+        positive("production.truck.time", self.production.truck.time);
+        for (name, stats) in self.weapons.named() {
+            positive(&format!("weapons.{}.range", name), stats.range);
+            positive(&format!("weapons.{}.fire_rate", name), stats.fire_rate);
+            if stats.cone_angle <= 0.0 {
+                positive(&format!("weapons.{}.projectile_speed", name), stats.projectile_speed);
+            }
+            positive(&format!("weapons.{}.recipe.time", name), stats.recipe.time);
+        }
+        for (name, stats) in self.ammo.named() {
+            positive(&format!("ammo.{}.recipe.time", name), stats.recipe.time);
+        }
+        for (name, stats) in self.buildings.named() {
+            positive(&format!("buildings.{}.size", name), stats.size);
+        }
+        for (name, stats) in self.enemies.named() {
+            positive(&format!("enemies.{}.radius", name), stats.radius);
+            positive(&format!("enemies.{}.speed", name), stats.speed);
+            match stats.ability {
+                Some(EnemyAbility::HuntMiners { sight }) => positive(&format!("enemies.{}.ability.sight", name), sight),
+                Some(EnemyAbility::Spit { range, fire_rate, projectile_speed, .. }) => {
+                    positive(&format!("enemies.{}.ability.range", name), range);
+                    positive(&format!("enemies.{}.ability.fire_rate", name), fire_rate);
+                    positive(&format!("enemies.{}.ability.projectile_speed", name), projectile_speed);
+                }
+                Some(EnemyAbility::Burrow { surface_distance }) => {
+                    positive(&format!("enemies.{}.ability.surface_distance", name), surface_distance);
+                }
+                Some(EnemyAbility::Split { .. }) | None => {}
+            }
+        }
+        positive("boss.stats.radius", self.boss.stats.radius);
+        positive("boss.stats.speed", self.boss.stats.speed);
+        positive("boss.charge_speed", self.boss.charge_speed);
+        positive("boss.summon_interval", self.boss.summon_interval);
+        if self.boss.stats.ability.is_some() {
+            problems.push("boss.stats.ability is not used; bosses fight in phases instead".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.boss.summon_below) || !(0.0..=1.0).contains(&self.boss.charge_below) {
+            problems.push(format!(
+                "boss.summon_below and boss.charge_below must be between 0 and 1 (got {} and {})",
+                self.boss.summon_below, self.boss.charge_below
+            ));
+        }
+        if self.boss.charge_below > self.boss.summon_below {
+            problems.push(format!(
+                "boss.charge_below ({}) must not be above boss.summon_below ({})",
+                self.boss.charge_below, self.boss.summon_below
+            ));
+        }
+        let mut total_weight = 0.0;
+        for archetype in EnemyArchetype::ALL {
+            let weight = self.waves.archetypes.get(archetype).weight;
+            if !(0.0..=1.0).contains(&weight) {
+                problems.push(format!("waves.archetypes.{}.weight must be between 0 and 1 (got {})", archetype.name().to_lowercase(), weight));
+            }
+            total_weight += weight;
+        }
+        if total_weight > 1.0 {
+            problems.push(format!("waves.archetypes weights must add up to 1 or less (got {})", total_weight));
+        }
+
+        if !(self.enemies.wall_patience >= 0.0 && self.enemies.wall_patience.is_finite()) {
+            problems.push(format!("enemies.wall_patience must be 0 or more (got {})", self.enemies.wall_patience));
+        }
+        if !(0.0..=1.0).contains(&self.truck.separation) {
+            problems.push(format!("truck.separation must be between 0 and 1 (got {})", self.truck.separation));
+        }
+        for (name, value) in [
+            ("enemies.wander_time", self.enemies.wander_time),
+            ("enemies.flee_time", self.enemies.flee_time),
+            ("enemies.regroup_time", self.enemies.regroup_time),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                problems.push(format!("{} must be 0 or more (got {})", name, value));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.enemies.flee_health) {
+            problems.push(format!("enemies.flee_health must be between 0 and 1 (got {})", self.enemies.flee_health));
+        }
+        if !(0.0..=1.0).contains(&self.enemies.separation) {
+            problems.push(format!("enemies.separation must be between 0 and 1 (got {})", self.enemies.separation));
+        }
+        if !(self.grace_period >= 0.0 && self.grace_period.is_finite()) {
+            problems.push(format!("grace_period must be 0 or more (got {})", self.grace_period));
+        }
+        if self.spawn_distance_min > self.spawn_distance_max {
+            problems.push(format!(
+                "spawn_distance_min ({}) must not exceed spawn_distance_max ({})",
+                self.spawn_distance_min, self.spawn_distance_max
+            ));
+        }
+        if self.truck.max_cargo == 0 {
+            problems.push("truck.max_cargo must be at least 1".to_string());
+        }
+        if self.truck.max_health == 0 {
+            problems.push("truck.max_health must be at least 1".to_string());
+        }
+        for (name, stats) in self.ammo.named() {
+            if stats.per_box == 0 {
+                problems.push(format!("ammo.{}.per_box must be at least 1", name));
+            }
+        }
+        for (name, stats) in self.weapons.named() {
+            if stats.damage == 0 {
+                problems.push(format!("weapons.{}.damage must be at least 1", name));
+            }
+            let per_box = self.ammo(stats.ammo).per_box;
+            if stats.max_ammo < per_box {
+                problems.push(format!(
+                    "weapons.{}.max_ammo ({}) must hold at least one box of {} {}",
+                    name, stats.max_ammo, per_box, stats.ammo.name().to_lowercase()
+                ));
+            }
+            if !(stats.splash_radius >= 0.0 && stats.splash_radius.is_finite()) {
+                problems.push(format!("weapons.{}.splash_radius must be 0 or more (got {})", name, stats.splash_radius));
+            }
+            if !(0.0..=1.0).contains(&stats.splash_falloff) {
+                problems.push(format!("weapons.{}.splash_falloff must be between 0 and 1 (got {})", name, stats.splash_falloff));
+            }
+            if !(0.0..=360.0).contains(&stats.cone_angle) {
+                problems.push(format!("weapons.{}.cone_angle must be between 0 and 360 (got {})", name, stats.cone_angle));
+            }
+        }
+        if self.turret.bullet_damage == 0 {
+            problems.push("turret.bullet_damage must be at least 1".to_string());
+        }
+        if self.turret.max_bullets < self.ammo.bullets.per_box {
+            problems.push(format!(
+                "turret.max_bullets ({}) must hold at least one box of {} bullets",
+                self.turret.max_bullets, self.ammo.bullets.per_box
+            ));
+        }
+        for (name, stats) in self.buildings.named() {
+            if stats.max_health == 0 {
+                problems.push(format!("buildings.{}.max_health must be at least 1", name));
+            }
+        }
+        for (name, stats) in self.enemies.named() {
+            if stats.health == 0 {
+                problems.push(format!("enemies.{}.health must be at least 1", name));
+            }
+            if let Some(blast) = stats.explosion {
+                if !(blast.radius > 0.0 && blast.radius.is_finite()) {
+                    problems.push(format!("enemies.{}.explosion.radius must be greater than 0 (got {})", name, blast.radius));
+                }
+                if !(0.0..=1.0).contains(&blast.falloff) {
+                    problems.push(format!("enemies.{}.explosion.falloff must be between 0 and 1 (got {})", name, blast.falloff));
+                }
+            }
+            if let Some(EnemyAbility::Spit { splash_radius, .. }) = stats.ability
+                && !(splash_radius >= 0.0 && splash_radius.is_finite()) {
+                problems.push(format!("enemies.{}.ability.splash_radius must be 0 or more (got {})", name, splash_radius));
+            }
+        }
+        if self.boss.stats.health == 0 {
+            problems.push("boss.stats.health must be at least 1".to_string());
+        }
+        if let Some(blast) = self.boss.stats.explosion {
+            if !(blast.radius > 0.0 && blast.radius.is_finite()) {
+                problems.push(format!("boss.stats.explosion.radius must be greater than 0 (got {})", blast.radius));
+            }
+            if !(0.0..=1.0).contains(&blast.falloff) {
+                problems.push(format!("boss.stats.explosion.falloff must be between 0 and 1 (got {})", blast.falloff));
+            }
+        }
+        if self.boss.summon_count == 0 {
+            problems.push("boss.summon_count must be at least 1".to_string());
+        }
+
+        if !(self.waves.break_time >= 0.0 && self.waves.break_time.is_finite()) {
+            problems.push(format!("waves.break_time must be 0 or more (got {})", self.waves.break_time));
+        }
+        if !(self.waves.spawn_spacing >= 0.0 && self.waves.spawn_spacing.is_finite()) {
+            problems.push(format!("waves.spawn_spacing must be 0 or more (got {})", self.waves.spawn_spacing));
+        }
+        if self.waves.waves.is_empty() {
+            problems.push("waves.waves must define at least one wave".to_string());
+        }
+        for (i, wave) in self.waves.waves.iter().enumerate() {
+            if wave.groups.iter().all(|g| g.total() == 0) {
+                problems.push(format!("waves.waves[{}] has no enemies", i));
+            }
+            for (j, group) in wave.groups.iter().enumerate() {
+                if !(group.spread >= 0.0 && group.spread <= 180.0) {
+                    problems.push(format!(
+                        "waves.waves[{}].groups[{}].spread must be between 0 and 180 (got {})",
+                        i, j, group.spread
+                    ));
+                }
+            }
+        }
+
+        if self.ore.field_distance_min <= 0.0 || self.ore.field_distance_min > self.ore.field_distance_max {
+            problems.push(format!(
+                "ore.field_distance_min ({}) must be greater than 0 and not exceed ore.field_distance_max ({})",
+                self.ore.field_distance_min, self.ore.field_distance_max
+            ));
+        }
+        if self.ore.richness_min == 0 || self.ore.richness_min > self.ore.richness_max {
+            problems.push(format!(
+                "ore.richness_min ({}) must be at least 1 and not exceed ore.richness_max ({})",
+                self.ore.richness_min, self.ore.richness_max
+            ));
+        }
+        if !(self.ore.discovery_interval >= 0.0 && self.ore.discovery_interval.is_finite()) {
+            problems.push(format!("ore.discovery_interval must be 0 or more (got {})", self.ore.discovery_interval));
+        }
+        if self.ore.discovery_distance_step < 0.0 {
+            problems.push(format!("ore.discovery_distance_step must be 0 or more (got {})", self.ore.discovery_distance_step));
+        }
+        if self.ore.weights.contains_key(&Scrap) {
+            problems.push("ore.weights must not include Scrap, which is salvaged rather than mined".to_string());
+        }
+        if self.ore.weights.values().all(|&weight| weight == 0) {
+            problems.push("ore.weights must give at least one ore a weight above 0".to_string());
+        }
+
+        if self.victory.survive_waves == Some(0) {
+            problems.push("victory.survive_waves must be at least 1 if set".to_string());
+        }
+        if self.victory.stockpile_iron == Some(0) {
+            problems.push("victory.stockpile_iron must be at least 1 if set".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(BalanceError::Invalid(problems))
+        }
+    }
+
+    pub fn production(&self, production_type: ProductionType) -> &ProductionStats {
+        match production_type {
+            ProductionType::Truck => &self.production.truck,
+            ProductionType::Weapon(weapon) => &self.weapon(weapon).recipe,
+            ProductionType::Ammo(ammo) => &self.ammo(ammo).recipe,
+        }
+    }
+
+    pub fn weapon(&self, weapon: WeaponType) -> &WeaponStats {
+        match weapon {
+            WeaponType::MachineGun => &self.weapons.machine_gun,
+            WeaponType::Cannon => &self.weapons.cannon,
+            WeaponType::Rifle => &self.weapons.rifle,
+            WeaponType::Flamethrower => &self.weapons.flamethrower,
+        }
+    }
+
+    pub fn ammo(&self, ammo: AmmoType) -> &AmmoStats {
+        match ammo {
+            AmmoType::Bullets => &self.ammo.bullets,
+            AmmoType::Shells => &self.ammo.shells,
+            AmmoType::Fuel => &self.ammo.fuel,
+        }
+    }
+
+    pub fn building(&self, building_type: BuildingType) -> &BuildingStats {
+        match building_type {
+            BuildingType::Beacon => &self.buildings.beacon,
+            BuildingType::Garage => &self.buildings.garage,
+            BuildingType::Factory => &self.buildings.factory,
+            BuildingType::Turret => &self.buildings.turret,
+            BuildingType::Wall => &self.buildings.wall,
+        }
+    }
+
+    pub fn enemy(&self, size: EnemySize) -> &EnemyStats {
+        match size {
+            EnemySize::Small => &self.enemies.small,
+            EnemySize::Medium => &self.enemies.medium,
+            EnemySize::Large => &self.enemies.large,
+        }
+    }
+
+    pub fn archetype(&self, archetype: EnemyArchetype) -> &EnemyStats {
+        match archetype {
+            EnemyArchetype::Scout => &self.enemies.scout,
+            EnemyArchetype::Tank => &self.enemies.tank,
+            EnemyArchetype::Spitter => &self.enemies.spitter,
+            EnemyArchetype::Burrower => &self.enemies.burrower,
+            EnemyArchetype::Splitter => &self.enemies.splitter,
+        }
+    }
+}
+
+impl WeaponTable {
+    fn named(&self) -> [(&'static str, &WeaponStats); 4] {
+        [
+            ("machine_gun", &self.machine_gun),
+            ("cannon", &self.cannon),
+            ("rifle", &self.rifle),
+            ("flamethrower", &self.flamethrower),
+        ]
+    }
+}
+
+impl AmmoTable {
+    fn named(&self) -> [(&'static str, &AmmoStats); 3] {
+        [("bullets", &self.bullets), ("shells", &self.shells), ("fuel", &self.fuel)]
+    }
+}
+
+impl BuildingTable {
+    fn named(&self) -> [(&'static str, &BuildingStats); 5] {
+        [
+            ("beacon", &self.beacon),
+            ("garage", &self.garage),
+            ("factory", &self.factory),
+            ("turret", &self.turret),
+            ("wall", &self.wall),
+        ]
+    }
+}
+
+impl EnemyTable {
+    fn named(&self) -> [(&'static str, &EnemyStats); 8] {
+        [
+            ("small", &self.small),
+            ("medium", &self.medium),
+            ("large", &self.large),
+            ("scout", &self.scout),
+            ("tank", &self.tank),
+            ("spitter", &self.spitter),
+            ("burrower", &self.burrower),
+            ("splitter", &self.splitter),
+        ]
+    }
+}
+
+impl ArchetypeSpawns {
+    /// Spawn settings for `archetype`.
+    pub fn get(&self, archetype: EnemyArchetype) -> &ArchetypeSpawn {
+        match archetype {
+            EnemyArchetype::Scout => &self.scout,
+            EnemyArchetype::Tank => &self.tank,
+            EnemyArchetype::Spitter => &self.spitter,
+            EnemyArchetype::Burrower => &self.burrower,
+            EnemyArchetype::Splitter => &self.splitter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(change: impl FnOnce(&mut Balance)) -> String {
+        let mut balance = Balance::default();
+        change(&mut balance);
+        balance.validate().expect_err("should be rejected").to_string()
+    }
+
+    #[test]
+    fn default_balance_is_valid() {
+        Balance::default().validate().unwrap();
+    }
+
+    #[test]
+    fn values_that_break_the_game_are_rejected() {
+        assert!(problems(|b| b.victory.stockpile_iron = Some(0)).contains("victory.stockpile_iron"));
+        assert!(problems(|b| b.turret.bullet_damage = 0).contains("turret.bullet_damage"));
+        assert!(problems(|b| b.boss.summon_count = 0).contains("boss.summon_count"));
+        assert!(problems(|b| b.boss.stats.explosion = Some(BlastStats { radius: 0.0, damage: 5, falloff: 2.0 }))
+            .contains("boss.stats.explosion.radius must be greater than 0 (got 0)"));
+        assert!(problems(|b| b.enemies.spitter.ability = Some(EnemyAbility::Spit {
+            range: 200.0, damage: 4, fire_rate: 2.0, projectile_speed: 150.0, splash_radius: -1.0,
+        })).contains("enemies.spitter.ability.splash_radius"));
+    }
+}
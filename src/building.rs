@@ -1,72 +1,59 @@
+use std::collections::BTreeMap;
 use egui::Pos2;
+use serde::{Deserialize, Serialize};
+use crate::balance::Balance;
+use crate::weapon::{AmmoType, WeaponType};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum BuildingType {
     Beacon,  // The starting base
     Garage,  // Builds trucks
-    Factory, // Makes guns and bullets
+    Factory, // Makes weapons and ammo
+    Turret,  // Fixed gun fed from nearby factories
+    Wall,    // Cheap segment that blocks enemies
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ProductionType {
     Truck,
-    Gun,
-    Bullets,
+    Weapon(WeaponType),
+    /// One box of ammo.
+    Ammo(AmmoType),
 }
 
 impl ProductionType {
-    pub fn cost(&self) -> (u32, u32) {
-        // Returns (iron, coal) cost
-        match self {
-            ProductionType::Truck => (20, 10),
-            ProductionType::Gun => (30, 5),
-            ProductionType::Bullets => (5, 10),
-        }
-    }
-    
-    pub fn time(&self) -> f32 {
-        match self {
-            ProductionType::Truck => 5.0,
-            ProductionType::Gun => 8.0,
-            ProductionType::Bullets => 3.0,
-        }
-    }
-    
     pub fn name(&self) -> &str {
         match self {
             ProductionType::Truck => "Truck",
-            ProductionType::Gun => "Gun",
-            ProductionType::Bullets => "Bullets",
+            ProductionType::Weapon(weapon) => weapon.name(),
+            ProductionType::Ammo(ammo) => ammo.name(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Building {
     pub position: Pos2,
     pub building_type: BuildingType,
     pub size: f32,
     pub production_queue: Vec<ProductionType>,
     pub production_progress: f32,
-    pub stored_guns: u32,
-    pub stored_bullet_boxes: u32,
+    /// Finished weapons waiting for a truck to pick them up.
+    pub stored_weapons: BTreeMap<WeaponType, u32>,
+    /// Finished ammo, in boxes.
+    pub stored_ammo: BTreeMap<AmmoType, u32>,
+    /// Loaded ammo, for turrets.
+    pub bullets: u32,
+    pub fire_cooldown: f32,
     pub health: u32,
     pub max_health: u32,
 }
 
 impl Building {
-    pub fn new(position: Pos2, building_type: BuildingType) -> Self {
-        let size = match building_type {
-            BuildingType::Beacon => 30.0,
-            BuildingType::Garage => 40.0,
-            BuildingType::Factory => 50.0,
-        };
-        
-        let max_health = match building_type {
-            BuildingType::Beacon => 1000,
-            BuildingType::Garage => 300,
-            BuildingType::Factory => 400,
-        };
+    pub fn new(position: Pos2, building_type: BuildingType, balance: &Balance) -> Self {
+        let stats = balance.building(building_type);
+        let size = stats.size;
+        let max_health = stats.max_health;
         
         Self {
             position,
@@ -74,8 +61,10 @@ impl Building {
             size,
             production_queue: Vec::new(),
             production_progress: 0.0,
-            stored_guns: 0,
-            stored_bullet_boxes: 0,
+            stored_weapons: BTreeMap::new(),
+            stored_ammo: BTreeMap::new(),
+            bullets: 0,
+            fire_cooldown: 0.0,
             health: max_health,
             max_health,
         }
@@ -87,21 +76,60 @@ impl Building {
         dx < self.size && dy < self.size
     }
     
-    pub fn cost(&self) -> (u32, u32) {
-        // Returns (iron, coal) cost
-        match self.building_type {
-            BuildingType::Beacon => (0, 0),
-            BuildingType::Garage => (50, 30),
-            BuildingType::Factory => (100, 50),
-        }
+    /// True if a circle at `center` overlaps the square footprint.
+    pub fn overlaps_circle(&self, center: Pos2, radius: f32) -> bool {
+        self.distance_to(center) < radius
+    }
+    
+    /// Distance from `point` to the nearest edge of the footprint; 0 inside it.
+    pub fn distance_to(&self, point: Pos2) -> f32 {
+        let dx = ((point.x - self.position.x).abs() - self.size).max(0.0);
+        let dy = ((point.y - self.position.y).abs() - self.size).max(0.0);
+        (dx * dx + dy * dy).sqrt()
     }
     
     pub fn can_produce(&self, production_type: ProductionType) -> bool {
-        match (self.building_type, production_type) {
-            (BuildingType::Garage, ProductionType::Truck) => true,
-            (BuildingType::Factory, ProductionType::Gun) => true,
-            (BuildingType::Factory, ProductionType::Bullets) => true,
-            _ => false,
+        matches!(
+            (self.building_type, production_type),
+            (BuildingType::Garage, ProductionType::Truck)
+                | (BuildingType::Factory, ProductionType::Weapon(_))
+                | (BuildingType::Factory, ProductionType::Ammo(_))
+        )
+    }
+}
+
+/// Centers of the wall segments from `start` to `end`, each `2 * size`
+/// wide. They are spread evenly, no further apart than their width, so
+/// neighbours touch or overlap and nothing slips between them.
+pub fn wall_segments(start: Pos2, end: Pos2, size: f32) -> Vec<Pos2> {
+    let length = (end - start).length();
+    let count = (length / (size * 2.0)).ceil() as usize + 1;
+    (0..count)
+        .map(|i| {
+            let t = if count > 1 { i as f32 / (count - 1) as f32 } else { 0.0 };
+            start + (end - start) * t
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Vec2;
+
+    #[test]
+    fn wall_segments_touch_their_neighbours() {
+        let size = 10.0;
+        for (dx, dy) in [(0.0, 0.0), (19.0, 0.0), (21.0, 0.0), (39.9, 0.0), (100.0, 35.0), (-73.0, 58.0)] {
+            let start = Pos2::new(5.0, 5.0);
+            let end = start + Vec2::new(dx, dy);
+            let segments = wall_segments(start, end, size);
+            assert_eq!(segments.first(), Some(&start));
+            assert!((*segments.last().unwrap() - end).length() < 1e-3);
+            for pair in segments.windows(2) {
+                let gap = pair[1] - pair[0];
+                assert!(gap.x.abs() <= 2.0 * size + 1e-3 && gap.y.abs() <= 2.0 * size + 1e-3, "gap {:?} from {:?} to {:?}", gap, start, end);
+            }
         }
     }
 }
@@ -1,94 +1,388 @@
 use egui::{Pos2, Vec2};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::balance::{Balance, BossTable, EnemyAbility, EnemyStats};
+use crate::building::{Building, BuildingType};
+use crate::navigation::Route;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EnemySize {
-    Small,  // 10 radius
-    Medium, // 50 radius
-    Large,  // 100 radius
+    Small,
+    Medium,
+    Large,
 }
 
-impl EnemySize {
-    pub fn radius(&self) -> f32 {
+/// Special enemies with their own stats and tricks, on top of the plain
+/// small, medium and large ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnemyArchetype {
+    /// Fast and fragile; hunts mining trucks.
+    Scout,
+    /// Slow and armored; shrugs off 1-damage shots.
+    Tank,
+    /// Hangs back and lobs acid from outside most weapons' reach.
+    Spitter,
+    /// Tunnels under defenses and surfaces inside the base.
+    Burrower,
+    /// Breaks into small enemies when killed.
+    Splitter,
+}
+
+impl EnemyArchetype {
+    pub const ALL: [EnemyArchetype; 5] = [
+        EnemyArchetype::Scout,
+        EnemyArchetype::Tank,
+        EnemyArchetype::Spitter,
+        EnemyArchetype::Burrower,
+        EnemyArchetype::Splitter,
+    ];
+
+    pub fn name(self) -> &'static str {
         match self {
-            EnemySize::Small => 10.0,
-            EnemySize::Medium => 25.0,
-            EnemySize::Large => 50.0,
+            EnemyArchetype::Scout => "Scout",
+            EnemyArchetype::Tank => "Tank",
+            EnemyArchetype::Spitter => "Spitter",
+            EnemyArchetype::Burrower => "Burrower",
+            EnemyArchetype::Splitter => "Splitter",
         }
     }
-    
-    pub fn health(&self) -> u32 {
+
+    /// Size class it counts as wherever plain sizes matter.
+    pub fn size(self) -> EnemySize {
         match self {
-            EnemySize::Small => 10,
-            EnemySize::Medium => 40,
-            EnemySize::Large => 100,
+            EnemyArchetype::Scout => EnemySize::Small,
+            EnemyArchetype::Spitter | EnemyArchetype::Burrower => EnemySize::Medium,
+            EnemyArchetype::Tank | EnemyArchetype::Splitter => EnemySize::Large,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Stage of a boss fight, moved through as the boss loses health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BossPhase {
+    /// Shield up: extra armor.
+    Shielded,
+    /// Calling up small enemies around itself.
+    Summoning,
+    /// Rushing the nearest factory.
+    Charging,
+}
+
+impl BossPhase {
+    pub fn name(self) -> &'static str {
+        match self {
+            BossPhase::Shielded => "Shielded",
+            BossPhase::Summoning => "Summoning",
+            BossPhase::Charging => "Charging",
+        }
+    }
+
+    /// Phase for a boss with `share` (0 to 1) of its health left.
+    pub fn for_health(share: f32, boss: &BossTable) -> Self {
+        if share <= boss.charge_below {
+            BossPhase::Charging
+        } else if share <= boss.summon_below {
+            BossPhase::Summoning
+        } else {
+            BossPhase::Shielded
+        }
+    }
+}
+
+/// What an enemy is doing; see `Enemy::think` for the transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EnemyBehavior {
+    /// Milling about near its spawn point, just after arriving.
     Wandering,
-    Fleeing,
+    /// Marching on the beacon.
+    Advancing,
+    /// Going after a truck or building it has spotted.
     Attacking,
+    /// Running from fire it can't win against.
+    Fleeing,
+    /// Catching up with its pack after fleeing.
+    Regrouping,
+}
+
+/// What an enemy notices around it this tick, gathered by the simulation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Senses {
+    /// Closest truck in sight.
+    pub truck: Option<Pos2>,
+    /// Closest building in sight, other than walls and the beacon.
+    pub building: Option<Pos2>,
+    /// Armed trucks and loaded turrets in sight.
+    pub threats: usize,
+    /// Other enemies within pack range.
+    pub pack_size: usize,
+    /// Middle of those other enemies.
+    pub pack_center: Option<Pos2>,
+    /// Closest factory anywhere; only looked up for bosses.
+    pub factory: Option<Pos2>,
 }
 
-#[derive(Debug, Clone)]
+/// Where an enemy wants to go this tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    /// The beacon, along the shared flow field.
+    Beacon,
+    /// Anywhere else, along the enemy's own route.
+    Point(Pos2),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Enemy {
     pub id: usize,
     pub position: Pos2,
     pub size: EnemySize,
+    /// Special kind, or `None` for a plain enemy of its size.
+    pub archetype: Option<EnemyArchetype>,
+    /// Current phase if this is a boss.
+    pub boss: Option<BossPhase>,
+    pub radius: f32,
     pub health: u32,
     pub max_health: u32,
     pub target: Option<Pos2>,
+    /// Way around buildings to `target`; planned by the simulation.
+    pub route: Route,
+    /// Seconds until a wandering enemy picks a new spot to stroll to.
     pub wander_timer: f32,
     pub behavior: EnemyBehavior,
+    /// Seconds spent in the current behavior.
+    pub behavior_time: f32,
+    /// Where it spawned; wandering stays near here.
+    pub home: Pos2,
+    /// Spot it is wandering or fleeing to.
+    pub waypoint: Option<Pos2>,
+    /// Whether it has already run away once.
+    pub has_fled: bool,
+    /// Hit by a shot since the last enemy update.
     pub being_shot_at: bool,
+    /// Underground: out of reach of weapons and free of walls.
+    pub burrowed: bool,
+    /// Seconds until its ability is ready again: a spitter's next glob or
+    /// a boss's next summon.
+    pub fire_cooldown: f32,
+    /// Seconds spent held up by walls; past `wall_patience` the enemy
+    /// stops and attacks the wall instead.
+    pub blocked_time: f32,
 }
 
 impl Enemy {
-    pub fn new(id: usize, position: Pos2, size: EnemySize) -> Self {
-        let max_health = size.health();
+    pub fn new(id: usize, position: Pos2, size: EnemySize, balance: &Balance) -> Self {
+        Self::with_stats(id, position, size, None, balance.enemy(size))
+    }
+
+    pub fn of_archetype(id: usize, position: Pos2, archetype: EnemyArchetype, balance: &Balance) -> Self {
+        let mut enemy = Self::with_stats(id, position, archetype.size(), Some(archetype), balance.archetype(archetype));
+        if let Some(EnemyAbility::Burrow { .. }) = enemy.stats(balance).ability {
+            enemy.burrowed = true;
+            enemy.behavior = EnemyBehavior::Advancing;
+        }
+        enemy
+    }
+
+    /// A boss. It skips wandering and heads straight for the beacon.
+    pub fn boss(id: usize, position: Pos2, balance: &Balance) -> Self {
+        let mut enemy = Self::with_stats(id, position, EnemySize::Large, None, &balance.boss.stats);
+        enemy.boss = Some(BossPhase::Shielded);
+        enemy.behavior = EnemyBehavior::Advancing;
+        enemy
+    }
+
+    fn with_stats(id: usize, position: Pos2, size: EnemySize, archetype: Option<EnemyArchetype>, stats: &EnemyStats) -> Self {
+        let max_health = stats.health;
         Self {
             id,
             position,
             size,
+            archetype,
+            boss: None,
+            radius: stats.radius,
             health: max_health,
             max_health,
             target: None,
+            route: Route::default(),
             wander_timer: 0.0,
             behavior: EnemyBehavior::Wandering,
+            behavior_time: 0.0,
+            home: position,
+            waypoint: None,
+            has_fled: false,
             being_shot_at: false,
+            burrowed: false,
+            fire_cooldown: 0.0,
+            blocked_time: 0.0,
         }
     }
-    
-    pub fn update(&mut self, delta_time: f32, beacon_pos: Pos2) {
-        // Always head towards the beacon (origin)
-        self.target = Some(beacon_pos);
+
+    /// Pick this tick's behavior from what the enemy senses and decide
+    /// where it is headed.
+    ///
+    /// New enemies wander near their spawn point for `wander_time`, then
+    /// advance on the beacon. Anything they spot on the way gets attacked.
+    /// Under fire, an enemy that is badly hurt or outgunned by the trucks
+    /// in sight runs away once, then regroups with its pack.
+    pub fn think(&mut self, delta_time: f32, senses: &Senses, balance: &Balance, rng: &mut impl Rng) -> Goal {
+        let stats = &balance.enemies;
+        self.behavior_time += delta_time;
+        let spotted = senses.truck.or(senses.building);
+
+        // A charging boss ignores everything on its way to the factory
+        if self.boss == Some(BossPhase::Charging) {
+            if self.behavior != EnemyBehavior::Attacking {
+                self.set_behavior(EnemyBehavior::Attacking);
+            }
+            return senses.factory.map_or(Goal::Beacon, Goal::Point);
+        }
+
+        let hurt = (self.health as f32) < self.max_health as f32 * stats.flee_health;
+        let outgunned = senses.threats > senses.pack_size + 1;
+        // Bosses stand their ground
+        if self.being_shot_at && !self.has_fled && self.boss.is_none() && (hurt || outgunned) {
+            // Run straight away from whatever is in sight, or back home
+            let speed = self.speed(balance);
+            self.waypoint = Some(match spotted {
+                Some(threat) if threat != self.position => {
+                    self.position + (self.position - threat).normalized() * speed * stats.flee_time
+                }
+                _ => self.home,
+            });
+            self.has_fled = true;
+            self.set_behavior(EnemyBehavior::Fleeing);
+        }
+
+        match self.behavior {
+            EnemyBehavior::Wandering | EnemyBehavior::Advancing if spotted.is_some() => {
+                self.set_behavior(EnemyBehavior::Attacking);
+            }
+            EnemyBehavior::Wandering if self.behavior_time >= stats.wander_time => {
+                self.set_behavior(EnemyBehavior::Advancing);
+            }
+            EnemyBehavior::Attacking if spotted.is_none() => {
+                self.set_behavior(EnemyBehavior::Advancing);
+            }
+            EnemyBehavior::Fleeing if self.behavior_time >= stats.flee_time => {
+                self.set_behavior(EnemyBehavior::Regrouping);
+            }
+            EnemyBehavior::Regrouping => {
+                let caught_up = senses.pack_center
+                    .is_none_or(|center| (center - self.position).length() < stats.pack_radius / 2.0);
+                if caught_up || self.behavior_time >= stats.regroup_time {
+                    self.set_behavior(EnemyBehavior::Advancing);
+                }
+            }
+            _ => {}
+        }
+
+        match self.behavior {
+            EnemyBehavior::Wandering => {
+                self.wander_timer -= delta_time;
+                let arrived = self.waypoint.is_none_or(|p| (p - self.position).length() < 2.0);
+                if arrived || self.wander_timer <= 0.0 {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    let distance = rng.gen_range(0.0..=stats.wander_radius);
+                    self.waypoint = Some(self.home + Vec2::angled(angle) * distance);
+                    self.wander_timer = rng.gen_range(1.0..3.0);
+                }
+                Goal::Point(self.waypoint.unwrap_or(self.home))
+            }
+            EnemyBehavior::Advancing => Goal::Beacon,
+            EnemyBehavior::Attacking => spotted.map_or(Goal::Beacon, Goal::Point),
+            EnemyBehavior::Fleeing => Goal::Point(self.waypoint.unwrap_or(self.position)),
+            EnemyBehavior::Regrouping => senses.pack_center.map_or(Goal::Beacon, Goal::Point),
+        }
+    }
+
+    fn set_behavior(&mut self, behavior: EnemyBehavior) {
+        self.behavior = behavior;
+        self.behavior_time = 0.0;
+    }
+
+    /// Move toward `heading` (the next step on the way to `target`) this tick.
+    pub fn update(&mut self, delta_time: f32, target: Pos2, heading: Pos2, nearby: &[&Building], balance: &Balance) {
+        self.target = Some(target);
         
-        // Move towards target
-        if let Some(target) = self.target {
-            let direction = target - self.position;
+        if (target - self.position).length() > 2.0 {
+            let direction = heading - self.position;
             let distance = direction.length();
             
-            if distance > 2.0 {
-                let speed = match self.size {
-                    EnemySize::Small => 20.0,
-                    EnemySize::Medium => 15.0,
-                    EnemySize::Large => 10.0,
-                };
+            if distance > 0.0 {
+                let speed = self.speed(balance);
                 
-                let movement = direction.normalized() * speed * delta_time;
-                if movement.length() < distance {
+                let movement = direction.normalized() * (speed * delta_time).min(distance);
+                if !self.hits_wall(self.position + movement, nearby) {
                     self.position += movement;
-                } else {
-                    self.position = target;
+                    self.blocked_time = 0.0;
+                    return;
+                }
+                
+                // Blocked: edge along the wall for a while, then stand and break it
+                self.blocked_time += delta_time;
+                if self.blocked_time < balance.enemies.wall_patience {
+                    // Always favour the same side so the enemy slides instead of jittering
+                    for degrees in [30.0_f32, 60.0, 90.0, 120.0, 150.0, -30.0, -60.0, -90.0, -120.0, -150.0] {
+                        let turned = Vec2::angled(movement.angle() + degrees.to_radians()) * movement.length();
+                        if !self.hits_wall(self.position + turned, nearby) {
+                            self.position += turned;
+                            break;
+                        }
+                    }
                 }
             }
         }
     }
     
+    /// Stats for a boss, its archetype, or its size if it is a plain enemy.
+    pub fn stats<'a>(&self, balance: &'a Balance) -> &'a EnemyStats {
+        match (self.boss, self.archetype) {
+            (Some(_), _) => &balance.boss.stats,
+            (None, Some(archetype)) => balance.archetype(archetype),
+            (None, None) => balance.enemy(self.size),
+        }
+    }
+
+    /// Armor, plus a boss's shield while it is up.
+    pub fn armor(&self, balance: &Balance) -> u32 {
+        let shield = if self.boss == Some(BossPhase::Shielded) { balance.boss.shield_armor } else { 0 };
+        self.stats(balance).armor + shield
+    }
+
+    pub fn speed(&self, balance: &Balance) -> f32 {
+        match self.boss {
+            Some(BossPhase::Charging) => balance.boss.charge_speed,
+            _ => self.stats(balance).speed,
+        }
+    }
+
+    /// Take a hit, less armor. Armor can soak a weak hit entirely.
+    pub fn take_damage(&mut self, damage: u32, balance: &Balance) {
+        self.being_shot_at = true;
+        self.health = self.health.saturating_sub(damage.saturating_sub(self.armor(balance)));
+    }
+
+    pub fn hits_wall(&self, position: Pos2, buildings: &[&Building]) -> bool {
+        buildings.iter()
+            .any(|b| b.building_type == BuildingType::Wall && b.overlaps_circle(position, self.radius))
+    }
+    
     pub fn radius(&self) -> f32 {
-        self.size.radius()
+        self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boss_phase_follows_health_thresholds() {
+        let boss = BossTable { summon_below: 0.7, charge_below: 0.3, ..BossTable::default() };
+        assert_eq!(BossPhase::for_health(1.0, &boss), BossPhase::Shielded);
+        assert_eq!(BossPhase::for_health(0.71, &boss), BossPhase::Shielded);
+        assert_eq!(BossPhase::for_health(0.7, &boss), BossPhase::Summoning);
+        assert_eq!(BossPhase::for_health(0.31, &boss), BossPhase::Summoning);
+        assert_eq!(BossPhase::for_health(0.3, &boss), BossPhase::Charging);
+        assert_eq!(BossPhase::for_health(0.0, &boss), BossPhase::Charging);
     }
 }
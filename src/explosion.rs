@@ -0,0 +1,58 @@
+use egui::Pos2;
+use serde::{Deserialize, Serialize};
+
+/// A blast that has gone off. Damage is dealt the tick it happens; the
+/// explosion itself only lingers so the renderer can draw it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explosion {
+    pub position: Pos2,
+    pub radius: f32,
+    /// Seconds since it went off.
+    pub age: f32,
+}
+
+impl Explosion {
+    /// Seconds an explosion stays on screen.
+    pub const DURATION: f32 = 0.5;
+
+    pub fn new(position: Pos2, radius: f32) -> Self {
+        Self { position, radius, age: 0.0 }
+    }
+
+    /// How far through its lifetime the explosion is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        (self.age / Self::DURATION).min(1.0)
+    }
+}
+
+/// Damage `distance` from the center of a blast: the full amount at the
+/// center, less by the `falloff` share (0 to 1) toward the edge, and none
+/// past `radius`. Anything caught takes at least 1.
+pub fn falloff_damage(damage: u32, radius: f32, falloff: f32, distance: f32) -> Option<u32> {
+    if distance > radius {
+        return None;
+    }
+    let edge = if radius > 0.0 { distance / radius } else { 0.0 };
+    let scaled = damage as f32 * (1.0 - falloff * edge);
+    Some((scaled.round() as u32).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_damage_scales_from_center_to_edge() {
+        assert_eq!(falloff_damage(20, 100.0, 0.5, 0.0), Some(20));
+        assert_eq!(falloff_damage(20, 100.0, 0.5, 50.0), Some(15));
+        assert_eq!(falloff_damage(20, 100.0, 0.5, 100.0), Some(10));
+        assert_eq!(falloff_damage(20, 100.0, 0.5, 100.5), None);
+    }
+
+    #[test]
+    fn falloff_damage_always_does_at_least_one() {
+        assert_eq!(falloff_damage(1, 100.0, 1.0, 100.0), Some(1));
+        assert_eq!(falloff_damage(0, 100.0, 0.0, 10.0), Some(1));
+        assert_eq!(falloff_damage(8, 0.0, 1.0, 0.0), Some(8));
+    }
+}
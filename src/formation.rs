@@ -0,0 +1,126 @@
+use egui::{Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Shape a group of trucks takes up when moved together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Formation {
+    /// Tight rings around the target.
+    #[default]
+    Cluster,
+    /// One rank side by side, across the facing direction.
+    Line,
+    /// Square-ish block of ranks.
+    Box,
+    /// V with the point toward the facing direction.
+    Wedge,
+}
+
+impl Formation {
+    pub const ALL: [Formation; 4] = [Formation::Cluster, Formation::Line, Formation::Box, Formation::Wedge];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formation::Cluster => "Cluster",
+            Formation::Line => "Line",
+            Formation::Box => "Box",
+            Formation::Wedge => "Wedge",
+        }
+    }
+
+    /// `count` slots centered on `center`, front rank first, so handing
+    /// them out in order puts the first trucks at the front. `facing` is
+    /// the direction the formation looks; it need not be normalized.
+    pub fn slots(self, center: Pos2, facing: Vec2, count: usize, spacing: f32) -> Vec<Pos2> {
+        let forward = if facing.length_sq() > 0.0 { facing.normalized() } else { -Vec2::Y };
+        let right = forward.rot90();
+        // (across, ahead) offsets in units of `spacing`
+        let offsets: Vec<(f32, f32)> = match self {
+            Formation::Cluster => return cluster_slots(center, count, spacing),
+            Formation::Line => rank(count, 0.0),
+            Formation::Box => {
+                let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+                (0..count.div_ceil(columns))
+                    .flat_map(|row| rank(columns.min(count - row * columns), -(row as f32)))
+                    .collect()
+            }
+            Formation::Wedge => (0..count)
+                .map(|i| {
+                    // Point first, then pairs spreading back left and right
+                    let step = i.div_ceil(2) as f32;
+                    let side = if i % 2 == 1 { -1.0 } else { 1.0 };
+                    (step * side, -step)
+                })
+                .collect(),
+        };
+
+        // Center the formation's depth on the target
+        let front = offsets.iter().map(|o| o.1).fold(0.0, f32::max);
+        let back = offsets.iter().map(|o| o.1).fold(0.0, f32::min);
+        let middle = (front + back) / 2.0;
+        offsets
+            .into_iter()
+            .map(|(across, ahead)| center + (right * across + forward * (ahead - middle)) * spacing)
+            .collect()
+    }
+}
+
+/// One rank of `count` slots centered across the facing direction,
+/// middle slots first.
+fn rank(count: usize, ahead: f32) -> Vec<(f32, f32)> {
+    let half = (count as f32 - 1.0) / 2.0;
+    let mut across: Vec<f32> = (0..count).map(|i| i as f32 - half).collect();
+    across.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+    across.into_iter().map(|x| (x, ahead)).collect()
+}
+
+/// Arrival slots for a group move: the first at `center`, the rest in
+/// rings around it, each ring as full as `spacing` allows.
+pub fn cluster_slots(center: Pos2, count: usize, spacing: f32) -> Vec<Pos2> {
+    let mut slots = Vec::with_capacity(count);
+    if count > 0 {
+        slots.push(center);
+    }
+    let mut ring = 1;
+    while slots.len() < count {
+        let radius = spacing * ring as f32;
+        let ring_slots = 6 * ring;
+        for i in 0..ring_slots.min(count - slots.len()) {
+            let angle = std::f32::consts::TAU * i as f32 / ring_slots as f32;
+            slots.push(center + Vec2::angled(angle) * radius);
+        }
+        ring += 1;
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_formation_gives_each_truck_its_own_room() {
+        let center = Pos2::new(100.0, -50.0);
+        for formation in Formation::ALL {
+            for count in [0, 1, 2, 7, 20] {
+                let slots = formation.slots(center, Vec2::new(1.0, 1.0), count, 30.0);
+                assert_eq!(slots.len(), count, "{:?}", formation);
+                for (i, a) in slots.iter().enumerate() {
+                    for b in &slots[i + 1..] {
+                        assert!((*a - *b).length() >= 29.9, "{:?} with {} trucks packs {:?} and {:?}", formation, count, a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines_run_across_and_wedges_point_along_the_facing() {
+        let center = Pos2::new(0.0, 0.0);
+        let line = Formation::Line.slots(center, Vec2::X, 5, 30.0);
+        assert!(line.iter().all(|slot| slot.x.abs() < 0.01));
+        assert_eq!(line[0], center);
+
+        let wedge = Formation::Wedge.slots(center, Vec2::X, 5, 30.0);
+        assert!(wedge[1..].iter().all(|slot| slot.x < wedge[0].x));
+    }
+}
@@ -1,83 +1,206 @@
 use eframe::egui;
 use egui::{Color32, Pos2, Rect, Vec2};
-use crate::truck::Truck;
-use crate::resource::{OrePatch, ResourceType};
-use crate::building::{Building, BuildingType, ProductionType};
-use crate::enemy::{Enemy, EnemySize};
-use rand::Rng;
+use crate::truck::{Stance, TruckOrder, TruckState};
+use crate::resource::ResourceType;
+use crate::building::{self, BuildingType, ProductionType};
+use crate::enemy::{BossPhase, EnemyArchetype, EnemyBehavior, EnemySize};
+use crate::simulation::{Command, Simulation};
+use crate::formation::Formation;
+use crate::balance::Balance;
+use crate::outcome::MatchOutcome;
+use crate::weapon::{AmmoType, WeaponType};
+use crate::save;
+use crate::replay::{self, Replay, ReplayPlayer};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BuildMode {
     None,
     PlacingGarage,
     PlacingFactory,
+    PlacingTurret,
+    PlacingWall,
 }
 
 pub struct GameApp {
-    pub trucks: Vec<Truck>,
-    pub ore_patches: Vec<OrePatch>,
-    pub buildings: Vec<Building>,
-    pub enemies: Vec<Enemy>,
-    pub next_truck_id: usize,
-    pub next_enemy_id: usize,
-    pub iron: u32,
-    pub coal: u32,
-    pub guns: u32,
-    pub bullets: u32,
+    pub sim: Simulation,
     dragging: bool,
     drag_start: Option<Pos2>,
     drag_end: Option<Pos2>,
     camera_offset: Vec2,
     panning: bool,
     pan_start: Option<Pos2>,
+    /// Screen position a right-drag move order started from.
+    order_drag_start: Option<Pos2>,
+    formation: Formation,
+    /// Next right-click or right-drag is an attack-move.
+    attack_move_armed: bool,
     build_mode: BuildMode,
     selected_building: Option<usize>,
-    enemy_spawn_timer: f32,
     camera_initialized: bool,
     zoom: f32,
-    game_timer: f32,
+    tick_accumulator: f32,
+    status_message: Option<String>,
+    /// Commands issued since the match started; `None` once that history
+    /// is broken (e.g. by loading a save).
+    recording: Option<Replay>,
+    replay_player: Option<ReplayPlayer>,
 }
 
-impl Default for GameApp {
-    fn default() -> Self {
-        let mut trucks = Vec::new();
-        let mut armed_truck = Truck::new(0, Pos2::new(50.0, 50.0));
-        armed_truck.has_gun = true;
-        armed_truck.bullets = 200;
-        trucks.push(armed_truck);
-        trucks.push(Truck::new(1, Pos2::new(100.0, 50.0)));
-        trucks.push(Truck::new(2, Pos2::new(75.0, 100.0)));
-        
-        let mut ore_patches = Vec::new();
-        ore_patches.push(OrePatch::new(Pos2::new(-150.0, 100.0), ResourceType::Iron));
-        ore_patches.push(OrePatch::new(Pos2::new(150.0, 100.0), ResourceType::Coal));
+impl GameApp {
+    /// Cap on catch-up ticks per frame so a long stall can't snowball.
+    const MAX_TICKS_PER_FRAME: u32 = 8;
+    
+    fn screen_to_world(&self, pos: Pos2) -> Pos2 {
+        ((pos - self.camera_offset).to_vec2() / self.zoom).to_pos2()
+    }
+
+    fn world_to_screen(&self, pos: Pos2) -> Pos2 {
+        (pos.to_vec2() * self.zoom + self.camera_offset).to_pos2()
+    }
+
+    /// Apply a player command to the simulation and record it for replays.
+    /// Player input is ignored while a replay is playing.
+    fn issue(&mut self, command: Command) -> bool {
+        if self.replay_player.is_some() {
+            return false;
+        }
+        let applied = self.sim.apply(&command);
+        if applied && let Some(recording) = &mut self.recording {
+            recording.record(self.sim.tick, command);
+        }
+        applied
+    }
+    
+    fn save_replay(&mut self) {
+        let Some(recording) = &self.recording else { return };
+        self.status_message = Some(match recording.save_to_file(replay::REPLAY_PATH) {
+            Ok(()) => format!("Replay saved to {}", replay::REPLAY_PATH),
+            Err(err) => format!("Replay save failed: {}", err),
+        });
+    }
+    
+    fn play_replay(&mut self) {
+        match Replay::load_from_file(replay::REPLAY_PATH) {
+            Ok(loaded) => {
+                let (player, sim) = ReplayPlayer::start(loaded);
+                self.sim = sim;
+                self.replay_player = Some(player);
+                self.recording = None;
+                self.reset_view_state();
+                self.status_message = Some(format!("Playing {}", replay::REPLAY_PATH));
+            }
+            Err(err) => self.status_message = Some(format!("Replay load failed: {}", err)),
+        }
+    }
+    
+    /// Forget UI state that refers to the previous world.
+    fn reset_view_state(&mut self) {
+        self.selected_building = None;
+        self.build_mode = BuildMode::None;
+        self.tick_accumulator = 0.0;
+    }
+    
+    /// End-of-match window with the final stats and a restart button.
+    fn show_match_summary(&mut self, ctx: &egui::Context, outcome: MatchOutcome) {
+        let title = match outcome {
+            MatchOutcome::Victory => "Victory!",
+            MatchOutcome::Defeat => "Beacon Destroyed",
+        };
+        let mut restart = false;
         
-        let mut buildings = Vec::new();
-        buildings.push(Building::new(Pos2::new(0.0, 0.0), BuildingType::Beacon));
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let stats = &self.sim.stats;
+                let minutes = self.sim.game_timer as u32 / 60;
+                let seconds = self.sim.game_timer as u32 % 60;
+                egui::Grid::new("match_stats").num_columns(2).show(ui, |ui| {
+                    ui.label("Time survived");
+                    ui.label(format!("{}:{:02}", minutes, seconds));
+                    ui.end_row();
+                    ui.label("Waves survived");
+                    ui.label(self.sim.waves_survived().to_string());
+                    ui.end_row();
+                    ui.label("Enemies killed");
+                    ui.label(stats.enemies_killed.to_string());
+                    ui.end_row();
+                    for resource_type in ResourceType::ALL {
+                        let verb = if resource_type.is_mined() { "mined" } else { "collected" };
+                        ui.label(format!("{} {}", resource_type.name(), verb));
+                        ui.label(stats.collected.get(resource_type).to_string());
+                        ui.end_row();
+                    }
+                    ui.label("Trucks built");
+                    ui.label(stats.trucks_built.to_string());
+                    ui.end_row();
+                    ui.label("Trucks lost");
+                    ui.label(stats.trucks_lost.to_string());
+                    ui.end_row();
+                    ui.label("Buildings lost");
+                    ui.label(stats.buildings_lost.to_string());
+                    ui.end_row();
+                });
+                ui.separator();
+                restart = ui.button("Restart").clicked();
+            });
         
+        if restart {
+            *self = GameApp::new(self.sim.balance.clone());
+        }
+    }
+    
+    fn save_game(&mut self) {
+        self.status_message = Some(match save::save_to_file(&self.sim, save::SAVE_PATH) {
+            Ok(()) => format!("Saved to {}", save::SAVE_PATH),
+            Err(err) => format!("Save failed: {}", err),
+        });
+    }
+    
+    fn load_game(&mut self) {
+        match save::load_from_file(save::SAVE_PATH) {
+            Ok(sim) => {
+                self.sim = sim;
+                // A replay must start from the initial seed, so loading ends it
+                self.recording = None;
+                self.replay_player = None;
+                self.reset_view_state();
+                self.status_message = Some(format!("Loaded {}", save::SAVE_PATH));
+            }
+            Err(err) => self.status_message = Some(format!("Load failed: {}", err)),
+        }
+    }
+}
+
+impl Default for GameApp {
+    fn default() -> Self {
+        Self::new(Balance::default())
+    }
+}
+
+impl GameApp {
+    pub fn new(balance: Balance) -> Self {
+        let seed = rand::random();
         Self {
-            trucks,
-            ore_patches,
-            buildings,
-            enemies: Vec::new(),
-            next_truck_id: 3,
-            next_enemy_id: 0,
-            iron: 0,
-            coal: 0,
-            guns: 0,
-            bullets: 0,
+            recording: Some(Replay::new(seed, balance.clone())),
+            sim: Simulation::new(seed, balance),
             dragging: false,
             drag_start: None,
             drag_end: None,
             camera_offset: Vec2::ZERO,
             panning: false,
             pan_start: None,
+            order_drag_start: None,
+            formation: Formation::default(),
+            attack_move_armed: false,
             build_mode: BuildMode::None,
             selected_building: None,
-            enemy_spawn_timer: 300.0,
             camera_initialized: false,
             zoom: 1.0,
-            game_timer: 0.0,
+            tick_accumulator: 0.0,
+            status_message: None,
+            replay_player: None,
         }
     }
 }
@@ -87,10 +210,36 @@ impl eframe::App for GameApp {
         // Request continuous repainting for smooth animation
         ctx.request_repaint();
         
-        let delta_time = ctx.input(|i| i.stable_dt);
+        // Quick-save / quick-load hotkeys
+        if ctx.input(|i| i.key_pressed(egui::Key::F5)) {
+            self.save_game();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::A)) {
+            self.attack_move_armed = !self.attack_move_armed;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::H)) {
+            self.issue(Command::SetStance { stance: Stance::HoldPosition });
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F9)) {
+            self.load_game();
+        }
         
-        // Update game timer
-        self.game_timer += delta_time;
+        // Run the simulation at a fixed rate regardless of frame rate
+        let delta_time = ctx.input(|i| i.stable_dt);
+        self.tick_accumulator += delta_time;
+        let mut ticks = 0;
+        while self.tick_accumulator >= Simulation::TICK_DT {
+            if ticks == Self::MAX_TICKS_PER_FRAME {
+                self.tick_accumulator = 0.0;
+                break;
+            }
+            if let Some(player) = &mut self.replay_player {
+                player.apply_due(&mut self.sim);
+            }
+            self.sim.step();
+            self.tick_accumulator -= Simulation::TICK_DT;
+            ticks += 1;
+        }
         
         // Center camera on beacon on first frame
         if !self.camera_initialized {
@@ -99,220 +248,72 @@ impl eframe::App for GameApp {
             self.camera_initialized = true;
         }
         
-        // Spawn enemies periodically far from beacon
-        self.enemy_spawn_timer -= delta_time;
-        if self.enemy_spawn_timer <= 0.0 {
-            let mut rng = rand::thread_rng();
-            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
-            let distance = rng.gen_range(1000.0..1500.0);
-            let pos = Pos2::new(angle.cos() * distance, angle.sin() * distance);
-            
-            let size = match rng.gen_range(0..10) {
-                0..=6 => EnemySize::Small,
-                7..=8 => EnemySize::Medium,
-                _ => EnemySize::Large,
-            };
-            
-            self.enemies.push(Enemy::new(self.next_enemy_id, pos, size));
-            self.next_enemy_id += 1;
-            self.enemy_spawn_timer = rng.gen_range(8.0..15.0);
-        }
-        
-        // Update enemies - they move towards beacon
-        let beacon_pos = Pos2::new(0.0, 0.0);
-        for enemy in &mut self.enemies {
-            enemy.update(delta_time, beacon_pos);
-            enemy.being_shot_at = false; // Reset each frame
-        }
-        
-        // Enemies attack buildings when in range
-        for enemy in &mut self.enemies {
-            for building in &mut self.buildings {
-                let distance = (enemy.position - building.position).length();
-                let attack_range = enemy.radius() + building.size;
-                
-                if distance < attack_range {
-                    // Deal damage based on enemy size
-                    let damage = match enemy.size {
-                        crate::enemy::EnemySize::Small => 1,
-                        crate::enemy::EnemySize::Medium => 2,
-                        crate::enemy::EnemySize::Large => 5,
-                    };
-                    building.health = building.health.saturating_sub(damage);
-                }
-            }
-        }
-        
-        // Remove destroyed buildings (except beacon for now)
-        self.buildings.retain(|b| b.health > 0 || b.building_type == BuildingType::Beacon);
-        
-        // Update all trucks
-        for truck in &mut self.trucks {
-            truck.update(delta_time);
-            
-            // Check if truck is at beacon to unload (any state, any amount)
-            let beacon_pos = Pos2::new(0.0, 0.0);
-            let distance = (truck.position - beacon_pos).length();
-            if distance < 35.0 && truck.cargo_amount > 0 {
-                // Unload cargo
-                match truck.cargo {
-                    Some(ResourceType::Iron) => self.iron += truck.cargo_amount,
-                    Some(ResourceType::Coal) => self.coal += truck.cargo_amount,
-                    None => {}
-                }
-                truck.cargo = None;
-                truck.cargo_amount = 0;
-                // If they were returning to base, they can now be idle
-                if truck.state == crate::truck::TruckState::ReturningToBase {
-                    truck.state = crate::truck::TruckState::Idle;
-                    truck.target = None;
-                }
-            }
-            
-            // Auto-return to last mining position if empty and idle at beacon
-            if distance < 35.0 && truck.state == crate::truck::TruckState::Idle 
-                && truck.cargo_amount == 0 && truck.last_mining_position.is_some() {
-                if let Some(mining_pos) = truck.last_mining_position {
-                    truck.start_moving(mining_pos);
-                }
-            }
-            
-            // Check if truck is at a factory to equip weapons
-            for building in &mut self.buildings {
-                if building.building_type == BuildingType::Factory {
-                    let factory_dist = (truck.position - building.position).length();
-                    // Allow equipping even while moving, just need to be close
-                    if factory_dist < 70.0 {
-                        // Equip gun if available and truck doesn't have one
-                        if !truck.has_gun && building.stored_guns > 0 {
-                            truck.has_gun = true;
-                            building.stored_guns -= 1;
-                        }
-                        
-                        // Load bullets if truck has gun and factory has bullets
-                        if truck.has_gun && truck.bullets < 400 && building.stored_bullet_boxes > 0 {
-                            let bullets_needed = 400 - truck.bullets;
-                            let boxes_to_load = (bullets_needed / 100).min(building.stored_bullet_boxes);
-                            if boxes_to_load > 0 {
-                                truck.bullets += boxes_to_load * 100;
-                                building.stored_bullet_boxes -= boxes_to_load;
-                            }
-                        }
-                    }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Factory Tank Miner");
+                ui.separator();
+                if ui.button("Save (F5)").clicked() {
+                    self.save_game();
                 }
-            }
-            
-            // Check if truck is on an ore patch and should start mining
-            if truck.state == crate::truck::TruckState::Idle && truck.cargo_amount < 64 {
-                for patch in &self.ore_patches {
-                    if patch.contains_point(truck.position) {
-                        truck.start_mining(patch.resource_type);
-                        break;
-                    }
+                if ui.button("Load (F9)").clicked() {
+                    self.load_game();
                 }
-            }
-        }
-        
-        // Armed trucks auto-fire at enemies in range
-        for truck in &mut self.trucks {
-            if truck.has_gun && truck.bullets > 0 && truck.fire_cooldown <= 0.0 {
-                // Find closest enemy in range
-                let mut closest_enemy_idx: Option<usize> = None;
-                let mut closest_distance = f32::MAX;
-                
-                for (idx, enemy) in self.enemies.iter().enumerate() {
-                    let distance = (enemy.position - truck.position).length();
-                    if distance <= Truck::WEAPON_RANGE && distance < closest_distance {
-                        closest_distance = distance;
-                        closest_enemy_idx = Some(idx);
-                    }
+                ui.separator();
+                if ui.add_enabled(self.recording.is_some(), egui::Button::new("Save Replay")).clicked() {
+                    self.save_replay();
                 }
-                
-                // Shoot at closest enemy
-                if let Some(enemy_idx) = closest_enemy_idx {
-                    truck.bullets -= 1;
-                    truck.fire_cooldown = Truck::FIRE_RATE;
-                    
-                    // Damage the enemy
-                    if let Some(enemy) = self.enemies.get_mut(enemy_idx) {
-                        enemy.being_shot_at = true;
-                        enemy.health = enemy.health.saturating_sub(1);
+                if let Some(player) = &self.replay_player {
+                    let state = if player.is_finished() { "finished" } else { "playing" };
+                    ui.label(format!("Replay {}: tick {} / {}", state, self.sim.tick, player.replay().last_tick()));
+                    if ui.button("Take Control").clicked() {
+                        self.replay_player = None;
                     }
+                } else if ui.button("Play Replay").clicked() {
+                    self.play_replay();
                 }
-            }
-        }
-        
-        // Remove dead enemies
-        self.enemies.retain(|e| e.health > 0);
-        
-        // Update buildings production
-        for building in &mut self.buildings {
-            if !building.production_queue.is_empty() {
-                let current = building.production_queue[0];
-                let production_time = current.time();
-                
-                building.production_progress += delta_time;
-                
-                if building.production_progress >= production_time {
-                    building.production_progress = 0.0;
-                    building.production_queue.remove(0);
-                    
-                    // Produce the item
-                    match current {
-                        ProductionType::Truck => {
-                            let offset_x = (self.next_truck_id as f32 % 3.0) * 30.0 - 30.0;
-                            let new_truck = Truck::new(
-                                self.next_truck_id,
-                                Pos2::new(building.position.x + offset_x, building.position.y + 60.0)
-                            );
-                            self.trucks.push(new_truck);
-                            self.next_truck_id += 1;
-                        }
-                        ProductionType::Gun => {
-                            building.stored_guns += 1;
-                        }
-                        ProductionType::Bullets => {
-                            building.stored_bullet_boxes += 1;
-                        }
-                    }
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
                 }
-            }
-        }
-        
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Factory Tank Miner");
+            });
             
             ui.horizontal(|ui| {
-                ui.label(format!("Iron: {}", self.iron));
-                ui.separator();
-                ui.label(format!("Coal: {}", self.coal));
-                ui.separator();
+                for resource_type in ResourceType::ALL {
+                    ui.label(format!("{}: {}", resource_type.name(), self.sim.stockpile.get(resource_type)));
+                    ui.separator();
+                }
                 
                 // Calculate total factory inventory
-                let total_guns: u32 = self.buildings.iter()
+                let total_weapons: u32 = self.sim.buildings.iter()
                     .filter(|b| b.building_type == BuildingType::Factory)
-                    .map(|b| b.stored_guns)
+                    .map(|b| b.stored_weapons.values().sum::<u32>())
                     .sum();
-                let total_bullets: u32 = self.buildings.iter()
+                let total_ammo: u32 = self.sim.buildings.iter()
                     .filter(|b| b.building_type == BuildingType::Factory)
-                    .map(|b| b.stored_bullet_boxes)
+                    .map(|b| b.stored_ammo.values().sum::<u32>())
                     .sum();
                 
-                ui.label(format!("Guns: {} | Bullets: {} boxes", total_guns, total_bullets));
+                ui.label(format!("Weapons: {} | Ammo: {} boxes", total_weapons, total_ammo));
                 ui.separator();
-                ui.label(format!("Trucks: {}", self.trucks.len()));
+                ui.label(format!("Trucks: {}", self.sim.trucks.len()));
                 ui.separator();
-                let selected_count = self.trucks.iter().filter(|t| t.selected).count();
+                let selected_count = self.sim.trucks.iter().filter(|t| t.selected).count();
                 ui.label(format!("Selected: {}", selected_count));
                 ui.separator();
-                let mining_count = self.trucks.iter().filter(|t| t.state == crate::truck::TruckState::Mining).count();
+                let mining_count = self.sim.trucks.iter().filter(|t| t.state == TruckState::Mining).count();
                 ui.label(format!("Mining: {}", mining_count));
                 ui.separator();
-                ui.label(format!("Enemies: {}", self.enemies.len()));
+                ui.label(format!("Enemies: {}", self.sim.enemies.len()));
+                ui.separator();
+                let waves = &self.sim.waves;
+                if waves.is_spawning() {
+                    ui.label(format!("Wave {}: {} incoming", waves.wave_number, waves.remaining_in_wave()));
+                } else {
+                    ui.label(format!("Next wave ({}) in {}s", waves.wave_number + 1, waves.countdown.ceil() as u32));
+                }
                 ui.separator();
                 
                 // Zoom controls (disabled for first 200 seconds)
-                let zoom_enabled = self.game_timer >= 200.0;
+                let zoom_enabled = self.sim.game_timer >= 200.0;
                 if ui.add_enabled(zoom_enabled, egui::Button::new("🔍+")).clicked() {
                     self.zoom = (self.zoom * 1.2).min(3.0);
                 }
@@ -320,38 +321,79 @@ impl eframe::App for GameApp {
                     self.zoom = (self.zoom / 1.2).max(0.5);
                 }
                 if !zoom_enabled {
-                    let time_left = (200.0 - self.game_timer) as u32;
+                    let time_left = (200.0 - self.sim.game_timer) as u32;
                     ui.label(format!("({}s)", time_left));
                 }
             });
+
+            // Boss health bar while one is on the field
+            for boss in self.sim.enemies.iter().filter(|e| e.boss.is_some()) {
+                let phase = boss.boss.map_or("", |p| p.name());
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::from_rgb(255, 80, 80), format!("BOSS ({})", phase));
+                    let share = boss.health as f32 / boss.max_health as f32;
+                    ui.add(egui::ProgressBar::new(share)
+                        .desired_width(400.0)
+                        .fill(Color32::from_rgb(160, 20, 40))
+                        .text(format!("{} / {}", boss.health, boss.max_health)));
+                });
+            }
             
             ui.separator();
             
             ui.horizontal(|ui| {
                 ui.label("Build:");
                 
-                let garage_cost = Building::new(Pos2::ZERO, BuildingType::Garage).cost();
-                let can_afford_garage = self.iron >= garage_cost.0 && self.coal >= garage_cost.1;
-                let garage_text = format!("Garage ({}Fe {}C)", garage_cost.0, garage_cost.1);
+                let garage_cost = &self.sim.balance.building(BuildingType::Garage).cost;
+                let can_afford_garage = self.sim.can_afford(garage_cost);
+                let garage_text = format!("Garage ({})", garage_cost);
                 
                 if ui.add_enabled(can_afford_garage && self.build_mode == BuildMode::None, 
                     egui::Button::new(garage_text)).clicked() {
                     self.build_mode = BuildMode::PlacingGarage;
                 }
                 
-                let factory_cost = Building::new(Pos2::ZERO, BuildingType::Factory).cost();
-                let can_afford_factory = self.iron >= factory_cost.0 && self.coal >= factory_cost.1;
-                let factory_text = format!("Factory ({}Fe {}C)", factory_cost.0, factory_cost.1);
+                let factory_cost = &self.sim.balance.building(BuildingType::Factory).cost;
+                let can_afford_factory = self.sim.can_afford(factory_cost);
+                let factory_text = format!("Factory ({})", factory_cost);
                 
                 if ui.add_enabled(can_afford_factory && self.build_mode == BuildMode::None,
                     egui::Button::new(factory_text)).clicked() {
                     self.build_mode = BuildMode::PlacingFactory;
                 }
                 
-                if self.build_mode != BuildMode::None {
-                    if ui.button("Cancel").clicked() {
-                        self.build_mode = BuildMode::None;
-                    }
+                let turret_cost = &self.sim.balance.building(BuildingType::Turret).cost;
+                let can_afford_turret = self.sim.can_afford(turret_cost);
+                let turret_text = format!("Turret ({})", turret_cost);
+                
+                if ui.add_enabled(can_afford_turret && self.build_mode == BuildMode::None,
+                    egui::Button::new(turret_text)).clicked() {
+                    self.build_mode = BuildMode::PlacingTurret;
+                }
+                
+                let wall_cost = &self.sim.balance.building(BuildingType::Wall).cost;
+                let can_afford_wall = self.sim.can_afford(wall_cost);
+                let wall_text = format!("Wall ({} each)", wall_cost);
+                
+                if ui.add_enabled(can_afford_wall && self.build_mode == BuildMode::None,
+                    egui::Button::new(wall_text)).clicked() {
+                    self.build_mode = BuildMode::PlacingWall;
+                }
+                
+                if self.build_mode != BuildMode::None && ui.button("Cancel").clicked() {
+                    self.build_mode = BuildMode::None;
+                }
+                
+                ui.separator();
+                ui.label("Formation:");
+                for formation in Formation::ALL {
+                    ui.selectable_value(&mut self.formation, formation, formation.name());
+                }
+                
+                ui.separator();
+                ui.toggle_value(&mut self.attack_move_armed, "Attack-move (A)");
+                if ui.button("Hold (H)").clicked() {
+                    self.issue(Command::SetStance { stance: Stance::HoldPosition });
                 }
             });
             
@@ -359,53 +401,48 @@ impl eframe::App for GameApp {
             
             // Building production UI
             if let Some(building_idx) = self.selected_building {
-                let building_type = self.buildings.get(building_idx).map(|b| b.building_type);
-                let queue_len = self.buildings.get(building_idx).map(|b| b.production_queue.len()).unwrap_or(0);
+                let building_type = self.sim.buildings.get(building_idx).map(|b| b.building_type);
+                let queue_len = self.sim.buildings.get(building_idx).map(|b| b.production_queue.len()).unwrap_or(0);
+                // What a factory has waiting for trucks
+                let stock: Vec<String> = self.sim.buildings.get(building_idx)
+                    .map(|b| {
+                        let weapons = b.stored_weapons.iter().map(|(w, n)| (w.name(), *n));
+                        let ammo = b.stored_ammo.iter().map(|(a, n)| (a.name(), *n));
+                        weapons.chain(ammo)
+                            .filter(|(_, n)| *n > 0)
+                            .map(|(name, n)| format!("{} {}", n, name))
+                            .collect()
+                    })
+                    .unwrap_or_default();
                 
                 if let Some(btype) = building_type {
                     ui.horizontal(|ui| {
                         ui.label(format!("Selected: {:?}", btype));
                         
-                        match btype {
-                            BuildingType::Garage => {
-                                let cost = ProductionType::Truck.cost();
-                                let can_afford = self.iron >= cost.0 && self.coal >= cost.1;
-                                if ui.add_enabled(can_afford, egui::Button::new(format!("Build Truck ({}Fe {}C)", cost.0, cost.1))).clicked() {
-                                    if let Some(b) = self.buildings.get_mut(building_idx) {
-                                        self.iron -= cost.0;
-                                        self.coal -= cost.1;
-                                        b.production_queue.push(ProductionType::Truck);
-                                    }
-                                }
+                        let options: Vec<ProductionType> = match btype {
+                            BuildingType::Garage => vec![ProductionType::Truck],
+                            BuildingType::Factory => WeaponType::ALL.into_iter().map(ProductionType::Weapon)
+                                .chain(AmmoType::ALL.into_iter().map(ProductionType::Ammo))
+                                .collect(),
+                            BuildingType::Beacon | BuildingType::Turret | BuildingType::Wall => Vec::new(),
+                        };
+                        for production_type in options {
+                            let cost = &self.sim.balance.production(production_type).cost;
+                            let label = format!("Build {} ({})", production_type.name(), cost);
+                            if ui.add_enabled(self.sim.can_afford(cost), egui::Button::new(label)).clicked() {
+                                self.issue(Command::QueueProduction { building_idx, production_type });
                             }
-                            BuildingType::Factory => {
-                                let gun_cost = ProductionType::Gun.cost();
-                                let can_afford_gun = self.iron >= gun_cost.0 && self.coal >= gun_cost.1;
-                                if ui.add_enabled(can_afford_gun, egui::Button::new(format!("Build Gun ({}Fe {}C)", gun_cost.0, gun_cost.1))).clicked() {
-                                    if let Some(b) = self.buildings.get_mut(building_idx) {
-                                        self.iron -= gun_cost.0;
-                                        self.coal -= gun_cost.1;
-                                        b.production_queue.push(ProductionType::Gun);
-                                    }
-                                }
-                                
-                                let bullet_cost = ProductionType::Bullets.cost();
-                                let can_afford_bullets = self.iron >= bullet_cost.0 && self.coal >= bullet_cost.1;
-                                if ui.add_enabled(can_afford_bullets, egui::Button::new(format!("Build Bullets ({}Fe {}C)", bullet_cost.0, bullet_cost.1))).clicked() {
-                                    if let Some(b) = self.buildings.get_mut(building_idx) {
-                                        self.iron -= bullet_cost.0;
-                                        self.coal -= bullet_cost.1;
-                                        b.production_queue.push(ProductionType::Bullets);
-                                    }
-                                }
-                            }
-                            BuildingType::Beacon => {}
                         }
                         
                         if queue_len > 0 {
                             ui.separator();
                             ui.label(format!("Queue: {}", queue_len));
                         }
+                        
+                        if !stock.is_empty() {
+                            ui.separator();
+                            ui.label(format!("Stock: {}", stock.join(", ")));
+                        }
                     });
                 }
             }
@@ -414,9 +451,12 @@ impl eframe::App for GameApp {
             ui.label("Controls:");
             ui.label("- Left click: Select single truck");
             ui.label("- Ctrl + Left click: Add/remove from selection");
-            ui.label("- Right click: Move selected trucks or place building");
-            ui.label("- Drag: Box select trucks");
+            ui.label("- Right click: Move selected trucks, attack an enemy, or place building");
+            ui.label("- A then right click: Attack-move / H: Hold position");
+            ui.label("- Right drag: Move selected trucks in formation, facing the drag direction");
+            ui.label("- Drag: Box select trucks (draws a wall while placing walls)");
             ui.label("- Middle mouse drag: Pan camera");
+            ui.label("- F5 / F9: Quick-save / quick-load");
             
             ui.separator();
             
@@ -430,7 +470,6 @@ impl eframe::App for GameApp {
             painter.rect_filled(canvas_rect, 0.0, Color32::from_rgb(30, 30, 35));
             
             // Draw grid
-            let grid_size = 64.0 * self.zoom;
             let grid_color = Color32::from_rgb(25, 25, 30);
             
             // Calculate world space bounds visible in the canvas
@@ -483,45 +522,121 @@ impl eframe::App for GameApp {
             }
             
             // Draw ore patches
-            for patch in &self.ore_patches {
+            for patch in &self.sim.ore_patches {
                 let screen_pos = Pos2::new(patch.position.x * self.zoom + self.camera_offset.x, patch.position.y * self.zoom + self.camera_offset.y);
                 
-                let color = match patch.resource_type {
-                    ResourceType::Iron => Color32::from_rgb(180, 140, 120),
-                    ResourceType::Coal => Color32::from_rgb(60, 60, 70),
-                };
+                let color = resource_color(patch.resource_type);
                 
-                painter.circle_filled(screen_pos, patch.size * self.zoom, color);
-                painter.circle_stroke(screen_pos, patch.size * self.zoom, (2.0, Color32::BLACK));
+                // Patches shrink and fade as they are mined out
+                let remaining = patch.remaining_fraction();
+                if patch.is_depleted() {
+                    painter.circle_stroke(screen_pos, patch.size * self.zoom, (1.0, Color32::from_gray(90)));
+                    continue;
+                }
+                let radius = patch.size * (0.4 + 0.6 * remaining.sqrt()) * self.zoom;
+                let color = color.gamma_multiply(0.5 + 0.5 * remaining);
+                painter.circle_filled(screen_pos, radius, color);
+                painter.circle_stroke(screen_pos, radius, (2.0, Color32::BLACK));
                 
                 // Draw label
-                let label = match patch.resource_type {
-                    ResourceType::Iron => "IRON",
-                    ResourceType::Coal => "COAL",
-                };
                 painter.text(
                     screen_pos,
                     egui::Align2::CENTER_CENTER,
-                    label,
+                    format!("{}\n{}", patch.resource_type.name().to_uppercase(), patch.amount),
                     egui::FontId::proportional(12.0),
                     Color32::WHITE,
                 );
             }
             
+            // Draw spilled cargo
+            for pile in &self.sim.resource_piles {
+                let screen_pos = Pos2::new(pile.position.x * self.zoom + self.camera_offset.x, pile.position.y * self.zoom + self.camera_offset.y);
+                let color = resource_color(pile.resource_type);
+                let half = 7.0 * self.zoom;
+                let diamond = vec![
+                    Pos2::new(screen_pos.x, screen_pos.y - half),
+                    Pos2::new(screen_pos.x + half, screen_pos.y),
+                    Pos2::new(screen_pos.x, screen_pos.y + half),
+                    Pos2::new(screen_pos.x - half, screen_pos.y),
+                ];
+                painter.add(egui::Shape::convex_polygon(diamond, color, (1.0, Color32::BLACK)));
+                painter.text(
+                    Pos2::new(screen_pos.x, screen_pos.y - half - 6.0),
+                    egui::Align2::CENTER_CENTER,
+                    format!("{}", pile.amount),
+                    egui::FontId::proportional(9.0),
+                    Color32::WHITE,
+                );
+            }
+            
             // Draw enemies
-            for enemy in &self.enemies {
+            for enemy in &self.sim.enemies {
                 let screen_pos = Pos2::new(enemy.position.x * self.zoom + self.camera_offset.x, enemy.position.y * self.zoom + self.camera_offset.y);
                 
-                // Red color, darker for larger enemies
-                let color = match enemy.size {
-                    EnemySize::Small => Color32::from_rgb(255, 100, 100),
-                    EnemySize::Medium => Color32::from_rgb(220, 60, 60),
-                    EnemySize::Large => Color32::from_rgb(180, 20, 20),
-                };
-                
                 let scaled_radius = enemy.radius() * self.zoom;
+
+                // Burrowed enemies only show as a mound of churned earth
+                if enemy.burrowed {
+                    painter.circle_filled(screen_pos, scaled_radius, Color32::from_rgba_unmultiplied(120, 80, 40, 90));
+                    painter.circle_stroke(screen_pos, scaled_radius, (1.0, Color32::from_rgb(90, 60, 30)));
+                    continue;
+                }
+
+                // Red, darker for larger enemies; archetypes and bosses get their own colors
+                let color = match (enemy.archetype, enemy.size) {
+                    _ if enemy.boss.is_some() => Color32::from_rgb(110, 0, 30),
+                    (Some(EnemyArchetype::Scout), _) => Color32::from_rgb(255, 170, 60),
+                    (Some(EnemyArchetype::Tank), _) => Color32::from_rgb(120, 90, 90),
+                    (Some(EnemyArchetype::Spitter), _) => Color32::from_rgb(130, 200, 60),
+                    (Some(EnemyArchetype::Burrower), _) => Color32::from_rgb(160, 110, 60),
+                    (Some(EnemyArchetype::Splitter), _) => Color32::from_rgb(180, 80, 200),
+                    (None, EnemySize::Small) => Color32::from_rgb(255, 100, 100),
+                    (None, EnemySize::Medium) => Color32::from_rgb(220, 60, 60),
+                    (None, EnemySize::Large) => Color32::from_rgb(180, 20, 20),
+                };
+
                 painter.circle_filled(screen_pos, scaled_radius, color);
-                painter.circle_stroke(screen_pos, scaled_radius, (2.0, Color32::from_rgb(100, 0, 0)));
+                // Fleeing enemies are outlined pale so it's clear they are breaking off
+                let outline = if enemy.behavior == EnemyBehavior::Fleeing {
+                    Color32::from_rgb(255, 220, 220)
+                } else {
+                    Color32::from_rgb(100, 0, 0)
+                };
+                // Tanks wear a thick plate ring
+                let outline_width = if enemy.archetype == Some(EnemyArchetype::Tank) { 5.0 } else { 2.0 };
+                painter.circle_stroke(screen_pos, scaled_radius, (outline_width, outline));
+                match enemy.archetype {
+                    // Splitters show the smalls waiting inside
+                    Some(EnemyArchetype::Splitter) => {
+                        for i in 0..3 {
+                            let offset = Vec2::angled(i as f32 * std::f32::consts::TAU / 3.0) * scaled_radius * 0.45;
+                            painter.circle_filled(screen_pos + offset, scaled_radius * 0.25, Color32::from_rgb(255, 100, 100));
+                        }
+                    }
+                    // Scouts point where they are going
+                    Some(EnemyArchetype::Scout) => {
+                        if let Some(target) = enemy.target
+                            && target != enemy.position {
+                            let tip = screen_pos + (target - enemy.position).normalized() * scaled_radius * 1.8;
+                            painter.line_segment([screen_pos, tip], (2.0, color));
+                        }
+                    }
+                    _ => {}
+                }
+                match enemy.boss {
+                    // Shield bubble
+                    Some(BossPhase::Shielded) => {
+                        painter.circle_filled(screen_pos, scaled_radius * 1.15, Color32::from_rgba_unmultiplied(90, 160, 255, 50));
+                        painter.circle_stroke(screen_pos, scaled_radius * 1.15, (3.0, Color32::from_rgb(120, 180, 255)));
+                    }
+                    Some(BossPhase::Summoning) => {
+                        painter.circle_stroke(screen_pos, scaled_radius * 1.15, (2.0, Color32::from_rgb(200, 120, 255)));
+                    }
+                    Some(BossPhase::Charging) => {
+                        painter.circle_stroke(screen_pos, scaled_radius * 1.1, (4.0, Color32::from_rgb(255, 140, 0)));
+                    }
+                    None => {}
+                }
                 
                 // Health bar
                 if enemy.health < enemy.max_health {
@@ -544,16 +659,20 @@ impl eframe::App for GameApp {
             }
             
             // Draw buildings
-            for (idx, building) in self.buildings.iter().enumerate() {
+            for (idx, building) in self.sim.buildings.iter().enumerate() {
                 let screen_pos = Pos2::new(building.position.x * self.zoom + self.camera_offset.x, building.position.y * self.zoom + self.camera_offset.y);
                 
-                let (color, mut label) = match building.building_type {
+                let (color, label) = match building.building_type {
                     BuildingType::Beacon => (Color32::from_rgb(255, 215, 0), "BEACON".to_string()),
                     BuildingType::Garage => (Color32::from_rgb(120, 120, 140), "GARAGE".to_string()),
                     BuildingType::Factory => {
-                        let label = format!("FACTORY\n({}G {}B)", building.stored_guns, building.stored_bullet_boxes);
+                        let weapons: u32 = building.stored_weapons.values().sum();
+                        let ammo: u32 = building.stored_ammo.values().sum();
+                        let label = format!("FACTORY\n({}W {}A)", weapons, ammo);
                         (Color32::from_rgb(140, 100, 80), label)
                     },
+                    BuildingType::Turret => (Color32::from_rgb(200, 110, 40), format!("T\n{}", building.bullets)),
+                    BuildingType::Wall => (Color32::from_rgb(110, 110, 110), String::new()),
                 };
                 
                 let rect = Rect::from_center_size(screen_pos, Vec2::splat(building.size * 2.0 * self.zoom));
@@ -567,6 +686,13 @@ impl eframe::App for GameApp {
                 };
                 painter.rect_stroke(rect, 2.0, (3.0, stroke_color));
                 
+                // Selected turrets show their firing and supply ranges
+                if Some(idx) == self.selected_building && building.building_type == BuildingType::Turret {
+                    let stats = &self.sim.balance.turret;
+                    painter.circle_stroke(screen_pos, stats.weapon_range * self.zoom, (1.5, Color32::from_rgb(255, 120, 60)));
+                    painter.circle_stroke(screen_pos, stats.supply_range * self.zoom, (1.0, Color32::from_rgba_unmultiplied(140, 100, 80, 120)));
+                }
+                
                 painter.text(
                     screen_pos,
                     egui::Align2::CENTER_CENTER,
@@ -578,7 +704,7 @@ impl eframe::App for GameApp {
                 // Show production progress
                 if !building.production_queue.is_empty() {
                     let current = building.production_queue[0];
-                    let progress = building.production_progress / current.time();
+                    let progress = building.production_progress / self.sim.balance.production(current).time;
                     let bar_width = building.size * 2.0 * self.zoom;
                     let bar_height = 6.0 * self.zoom;
                     
@@ -618,18 +744,17 @@ impl eframe::App for GameApp {
             // Handle input
             let pointer_pos = response.hover_pos();
             let ctrl_held = ui.input(|i| i.modifiers.ctrl);
-            let right_button = ui.input(|i| i.pointer.button_down(egui::PointerButton::Secondary));
+            let middle_button = ui.input(|i| i.pointer.button_down(egui::PointerButton::Middle));
             
-            // Handle camera panning with right mouse button
-            if right_button && !self.panning {
-                if let Some(pos) = pointer_pos {
-                    self.panning = true;
-                    self.pan_start = Some(pos);
-                }
+            // Handle camera panning with middle mouse button
+            if middle_button && !self.panning
+                && let Some(pos) = pointer_pos {
+                self.panning = true;
+                self.pan_start = Some(pos);
             }
             
             if self.panning {
-                if right_button {
+                if middle_button {
                     if let (Some(start), Some(current)) = (self.pan_start, pointer_pos) {
                         let delta = current - start;
                         self.camera_offset += delta;
@@ -642,19 +767,31 @@ impl eframe::App for GameApp {
             }
             
             // Handle drag selection
-            if response.drag_started() && !ctrl_held && !self.panning {
-                if let Some(pos) = pointer_pos {
-                    self.dragging = true;
-                    self.drag_start = Some(pos);
-                    self.drag_end = Some(pos);
-                }
+            if response.drag_started_by(egui::PointerButton::Primary) && !ctrl_held && !self.panning
+                && let Some(pos) = pointer_pos {
+                self.dragging = true;
+                self.drag_start = Some(pos);
+                self.drag_end = Some(pos);
             }
             
-            if response.dragged() && self.dragging {
+            if response.dragged_by(egui::PointerButton::Primary) && self.dragging {
                 self.drag_end = pointer_pos;
             }
             
-            if response.drag_stopped() && self.dragging {
+            if response.drag_stopped_by(egui::PointerButton::Primary) && self.dragging && self.build_mode == BuildMode::PlacingWall {
+                // In wall mode a drag lays a wall instead of selecting
+                if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
+                    let (world_start, world_end) = (self.screen_to_world(start), self.screen_to_world(end));
+                    if self.issue(Command::PlaceWall { start: world_start, end: world_end }) {
+                        self.build_mode = BuildMode::None;
+                    }
+                }
+                self.dragging = false;
+                self.drag_start = None;
+                self.drag_end = None;
+            }
+            
+            if response.drag_stopped_by(egui::PointerButton::Primary) && self.dragging {
                 if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
                     // Convert screen space selection to world space
                     let world_start = Pos2::new(start.x - self.camera_offset.x, start.y - self.camera_offset.y);
@@ -669,9 +806,11 @@ impl eframe::App for GameApp {
                         Pos2::new(max_x, max_y)
                     );
                     
-                    for truck in &mut self.trucks {
-                        truck.selected = selection_rect.intersects(truck.bounds());
-                    }
+                    let truck_ids = self.sim.trucks.iter()
+                        .filter(|t| selection_rect.intersects(t.bounds()))
+                        .map(|t| t.id)
+                        .collect();
+                    self.issue(Command::SelectTrucks { truck_ids, toggle: false });
                 }
                 
                 self.dragging = false;
@@ -680,152 +819,232 @@ impl eframe::App for GameApp {
             }
             
             // Handle single click selection
-            if response.clicked() && !self.dragging && !self.panning {
-                if let Some(pos) = pointer_pos {
-                    let world_pos = Pos2::new(pos.x - self.camera_offset.x, pos.y - self.camera_offset.y);
-                    
-                    // Check if clicking on a building first
-                    let mut clicked_building = None;
-                    for (i, building) in self.buildings.iter().enumerate() {
-                        if building.contains_point(world_pos) {
-                            clicked_building = Some(i);
-                            break;
-                        }
+            if response.clicked() && !self.dragging && !self.panning
+                && let Some(pos) = pointer_pos {
+                let world_pos = Pos2::new(pos.x - self.camera_offset.x, pos.y - self.camera_offset.y);
+                
+                // Check if clicking on a building first
+                let mut clicked_building = None;
+                for (i, building) in self.sim.buildings.iter().enumerate() {
+                    if building.contains_point(world_pos) {
+                        clicked_building = Some(i);
+                        break;
                     }
+                }
+                
+                if let Some(i) = clicked_building {
+                    self.selected_building = Some(i);
+                } else {
+                    self.selected_building = None;
                     
-                    if let Some(i) = clicked_building {
-                        self.selected_building = Some(i);
-                    } else {
-                        self.selected_building = None;
-                        
-                        // Try to select a truck
-                        let mut clicked_truck = None;
-                        for (i, truck) in self.trucks.iter().enumerate() {
-                            if truck.contains_point(world_pos) {
-                                clicked_truck = Some(i);
-                                break;
-                            }
-                        }
-                    
-                    if let Some(i) = clicked_truck {
-                        if ctrl_held {
-                            self.trucks[i].selected = !self.trucks[i].selected;
-                        } else {
-                            for truck in &mut self.trucks {
-                                truck.selected = false;
-                            }
-                            self.trucks[i].selected = true;
-                        }
+                    // Try to select a truck
+                    let clicked_truck = self.sim.trucks.iter()
+                        .find(|t| t.contains_point(world_pos))
+                        .map(|t| t.id);
+                
+                    if let Some(id) = clicked_truck {
+                        self.issue(Command::SelectTrucks { truck_ids: vec![id], toggle: ctrl_held });
                     } else if !ctrl_held {
-                        for truck in &mut self.trucks {
-                            truck.selected = false;
-                        }
-                    }
+                        self.issue(Command::SelectTrucks { truck_ids: Vec::new(), toggle: false });
                     }
                 }
             }
             
             // Handle right click to move or place building
-            if response.secondary_clicked() && !self.panning {
-                if let Some(target_pos) = pointer_pos {
-                    let world_target = Pos2::new(target_pos.x - self.camera_offset.x, target_pos.y - self.camera_offset.y);
-                    
-                    match self.build_mode {
-                        BuildMode::PlacingGarage => {
-                            let cost = Building::new(Pos2::ZERO, BuildingType::Garage).cost();
-                            if self.iron >= cost.0 && self.coal >= cost.1 {
-                                self.iron -= cost.0;
-                                self.coal -= cost.1;
-                                self.buildings.push(Building::new(world_target, BuildingType::Garage));
-                                self.build_mode = BuildMode::None;
-                            }
-                        }
-                        BuildMode::PlacingFactory => {
-                            let cost = Building::new(Pos2::ZERO, BuildingType::Factory).cost();
-                            if self.iron >= cost.0 && self.coal >= cost.1 {
-                                self.iron -= cost.0;
-                                self.coal -= cost.1;
-                                self.buildings.push(Building::new(world_target, BuildingType::Factory));
-                                self.build_mode = BuildMode::None;
-                            }
-                        }
-                        BuildMode::None => {
-                            // Normal truck movement
-                            for truck in &mut self.trucks {
-                                if truck.selected {
-                                    truck.start_moving(world_target);
-                                }
-                            }
-                        }
+            if response.secondary_clicked() && !self.panning
+                && let Some(target_pos) = pointer_pos {
+                let world_target = Pos2::new(target_pos.x - self.camera_offset.x, target_pos.y - self.camera_offset.y);
+                
+                let building_type = match self.build_mode {
+                    BuildMode::PlacingGarage => Some(BuildingType::Garage),
+                    BuildMode::PlacingFactory => Some(BuildingType::Factory),
+                    BuildMode::PlacingTurret => Some(BuildingType::Turret),
+                    BuildMode::PlacingWall => Some(BuildingType::Wall),
+                    BuildMode::None => None,
+                };
+                
+                if let Some(building_type) = building_type {
+                    if self.issue(Command::PlaceBuilding { building_type, position: world_target }) {
+                        self.build_mode = BuildMode::None;
                     }
+                } else {
+                    // Right-clicking an enemy sends armed trucks after it
+                    let clicked_enemy = self.sim.enemies.iter()
+                        .find(|e| !e.burrowed && (e.position - world_target).length() <= e.radius())
+                        .map(|e| e.id);
+                    let attacked = clicked_enemy.is_some_and(|enemy_id| self.issue(Command::AttackEnemy { enemy_id }));
+                    if !attacked {
+                        self.issue(Command::MoveSelected {
+                            target: world_target,
+                            formation: self.formation,
+                            facing: None,
+                            attack: self.attack_move_armed,
+                        });
+                    }
+                    self.attack_move_armed = false;
                 }
             }
             
-            // Draw selection box
-            if self.dragging {
-                if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
-                    let min_x = start.x.min(end.x);
-                    let max_x = start.x.max(end.x);
-                    let min_y = start.y.min(end.y);
-                    let max_y = start.y.max(end.y);
-                    let rect = Rect::from_min_max(
-                        Pos2::new(min_x, min_y),
-                        Pos2::new(max_x, max_y)
-                    );
-                    painter.rect_stroke(rect, 0.0, (1.0, Color32::WHITE));
-                    painter.rect_filled(rect, 0.0, Color32::from_rgba_premultiplied(255, 255, 255, 20));
+            // Right-drag gives a formation move facing the drag direction
+            if response.drag_started_by(egui::PointerButton::Secondary) && self.build_mode == BuildMode::None {
+                self.order_drag_start = ui.input(|i| i.pointer.press_origin());
+            }
+            
+            if response.drag_stopped_by(egui::PointerButton::Secondary)
+                && let Some(start) = self.order_drag_start.take()
+                && let Some(end) = pointer_pos {
+                let world_target = Pos2::new(start.x - self.camera_offset.x, start.y - self.camera_offset.y);
+                self.issue(Command::MoveSelected {
+                    target: world_target,
+                    formation: self.formation,
+                    facing: Some(end - start),
+                    attack: self.attack_move_armed,
+                });
+                self.attack_move_armed = false;
+            }
+            
+            // Preview the slots a right-drag would send trucks to
+            if let Some(start) = self.order_drag_start
+                && let Some(end) = pointer_pos {
+                let world_target = Pos2::new(start.x - self.camera_offset.x, start.y - self.camera_offset.y);
+                painter.arrow(start, end - start, (2.0, Color32::from_rgb(100, 255, 100)));
+                for (i, slot) in self.sim.formation_slots(world_target, self.formation, Some(end - start)) {
+                    let screen_slot = Pos2::new(slot.x * self.zoom + self.camera_offset.x, slot.y * self.zoom + self.camera_offset.y);
+                    let color = if self.sim.trucks[i].weapon.is_some() {
+                        Color32::from_rgb(255, 140, 0)
+                    } else {
+                        Color32::from_rgb(100, 150, 255)
+                    };
+                    painter.circle_stroke(screen_slot, self.sim.trucks[i].size / 2.0 * self.zoom, (1.5, color));
                 }
             }
             
-            // Draw bullet tracers from trucks to enemies they're shooting
-            for truck in &self.trucks {
-                if truck.has_gun && truck.fire_cooldown > Truck::FIRE_RATE - 0.1 {
-                    // Find closest enemy in range to draw tracer
-                    let mut closest_enemy_pos: Option<Pos2> = None;
-                    let mut closest_distance = f32::MAX;
-                    
-                    for enemy in &self.enemies {
-                        let distance = (enemy.position - truck.position).length();
-                        if distance <= Truck::WEAPON_RANGE && distance < closest_distance {
-                            closest_distance = distance;
-                            closest_enemy_pos = Some(enemy.position);
-                        }
-                    }
-                    
-                    if let Some(enemy_pos) = closest_enemy_pos {
-                        let truck_screen = Pos2::new(truck.position.x * self.zoom + self.camera_offset.x, truck.position.y * self.zoom + self.camera_offset.y);
-                        let enemy_screen = Pos2::new(enemy_pos.x * self.zoom + self.camera_offset.x, enemy_pos.y * self.zoom + self.camera_offset.y);
-                        painter.line_segment([truck_screen, enemy_screen], (2.0, Color32::from_rgb(255, 255, 0)));
-                    }
+            // Draw wall preview while dragging one out
+            if self.dragging && self.build_mode == BuildMode::PlacingWall
+                && let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
+                // Lay the segments out in the world, as placing them will, then draw them back on screen
+                let stats = self.sim.balance.building(BuildingType::Wall);
+                let segments = building::wall_segments(self.screen_to_world(start), self.screen_to_world(end), stats.size);
+                let affordable = (0..=segments.len())
+                    .rev()
+                    .find(|&n| self.sim.can_afford(&(&stats.cost * n as u32)))
+                    .unwrap_or(0);
+                for (i, segment) in segments.iter().enumerate() {
+                    let color = if i < affordable {
+                        Color32::from_rgba_premultiplied(100, 255, 100, 100)
+                    } else {
+                        Color32::from_rgba_premultiplied(255, 80, 80, 100)
+                    };
+                    let rect = Rect::from_center_size(self.world_to_screen(*segment), Vec2::splat(stats.size * 2.0 * self.zoom));
+                    painter.rect_filled(rect, 0.0, color);
                 }
             }
             
+            // Draw selection box
+            if self.dragging && self.build_mode != BuildMode::PlacingWall
+                && let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
+                let min_x = start.x.min(end.x);
+                let max_x = start.x.max(end.x);
+                let min_y = start.y.min(end.y);
+                let max_y = start.y.max(end.y);
+                let rect = Rect::from_min_max(
+                    Pos2::new(min_x, min_y),
+                    Pos2::new(max_x, max_y)
+                );
+                painter.rect_stroke(rect, 0.0, (1.0, Color32::WHITE));
+                painter.rect_filled(rect, 0.0, Color32::from_rgba_premultiplied(255, 255, 255, 20));
+            }
+            
+            // Draw projectiles as short streaks behind their heads
+            for projectile in &self.sim.projectiles {
+                let head = Pos2::new(projectile.position.x * self.zoom + self.camera_offset.x, projectile.position.y * self.zoom + self.camera_offset.y);
+                let tail = head - projectile.velocity.normalized() * 8.0 * self.zoom;
+                painter.line_segment([tail, head], (2.0, Color32::from_rgb(255, 255, 0)));
+            }
+            
+            // Spitter acid as green globs
+            for glob in &self.sim.spit {
+                let screen_pos = Pos2::new(glob.position.x * self.zoom + self.camera_offset.x, glob.position.y * self.zoom + self.camera_offset.y);
+                painter.circle_filled(screen_pos, 4.0 * self.zoom, Color32::from_rgb(150, 230, 60));
+            }
+            
+            // Explosions swell and fade out
+            for explosion in &self.sim.explosions {
+                let screen_pos = Pos2::new(explosion.position.x * self.zoom + self.camera_offset.x, explosion.position.y * self.zoom + self.camera_offset.y);
+                let progress = explosion.progress();
+                let radius = explosion.radius * (0.5 + 0.5 * progress) * self.zoom;
+                let alpha = ((1.0 - progress) * 160.0) as u8;
+                painter.circle_filled(screen_pos, radius, Color32::from_rgba_unmultiplied(255, 150, 40, alpha));
+                painter.circle_stroke(screen_pos, radius, (2.0, Color32::from_rgba_unmultiplied(255, 230, 120, alpha)));
+            }
+            
+            // Flamethrowers that just fired show their cone
+            for truck in &self.sim.trucks {
+                let Some(weapon) = truck.weapon else { continue };
+                let stats = self.sim.balance.weapon(weapon);
+                if stats.cone_angle <= 0.0 || truck.fire_cooldown <= 0.0 || truck.aim == Vec2::ZERO {
+                    continue;
+                }
+                let screen_pos = Pos2::new(truck.position.x * self.zoom + self.camera_offset.x, truck.position.y * self.zoom + self.camera_offset.y);
+                let facing = truck.aim.angle();
+                let half_angle = stats.cone_angle.to_radians() / 2.0;
+                let mut points = vec![screen_pos];
+                for step in 0..=8 {
+                    let angle = facing - half_angle + half_angle * 2.0 * step as f32 / 8.0;
+                    points.push(screen_pos + Vec2::angled(angle) * stats.range * self.zoom);
+                }
+                painter.add(egui::Shape::convex_polygon(points, Color32::from_rgba_unmultiplied(255, 120, 20, 90), egui::Stroke::NONE));
+            }
+            
             // Draw trucks
-            for truck in &self.trucks {
+            for truck in &self.sim.trucks {
                 let screen_pos = Pos2::new(truck.position.x * self.zoom + self.camera_offset.x, truck.position.y * self.zoom + self.camera_offset.y);
                 
                 // Choose color: armed=orange, with cargo=cargo color, else=blue, selected=green
-                let color = if truck.has_gun {
+                let color = if truck.weapon.is_some() {
                     Color32::from_rgb(255, 140, 0) // Orange for armed trucks
                 } else if truck.selected {
                     Color32::from_rgb(100, 255, 100)
                 } else {
                     match truck.cargo {
-                        Some(ResourceType::Iron) => Color32::from_rgb(180, 140, 120),
                         Some(ResourceType::Coal) => Color32::from_rgb(80, 80, 90),
+                        Some(resource_type) => resource_color(resource_type),
                         None => Color32::from_rgb(100, 150, 255),
                     }
                 };
                 
+                // Damaged trucks darken as they lose health
+                let health_percent = truck.health as f32 / truck.max_health as f32;
+                let color = color.gamma_multiply(0.4 + 0.6 * health_percent);
+                
                 // Draw truck body
                 let scaled_size = truck.size * self.zoom;
                 let bounds = Rect::from_center_size(screen_pos, Vec2::splat(scaled_size));
                 painter.rect_filled(bounds, 2.0, color);
                 painter.rect_stroke(bounds, 2.0, (2.0, Color32::BLACK));
                 
+                // Health bar
+                if truck.health < truck.max_health {
+                    let bar_width = scaled_size;
+                    let bar_height = 3.0 * self.zoom;
+                    let bar_top = screen_pos.y - scaled_size / 2.0 - 7.0 * self.zoom;
+                    
+                    let bg_rect = Rect::from_min_size(
+                        Pos2::new(screen_pos.x - bar_width / 2.0, bar_top),
+                        Vec2::new(bar_width, bar_height)
+                    );
+                    painter.rect_filled(bg_rect, 0.0, Color32::from_rgb(50, 50, 50));
+                    
+                    let health_rect = Rect::from_min_size(
+                        Pos2::new(screen_pos.x - bar_width / 2.0, bar_top),
+                        Vec2::new(bar_width * health_percent, bar_height)
+                    );
+                    painter.rect_filled(health_rect, 0.0, Color32::from_rgb(0, 200, 0));
+                }
+                
                 // Draw cargo and ammo info
-                if truck.has_gun && truck.cargo_amount > 0 {
-                    // Show both ore and bullets
+                if let Some(weapon) = truck.weapon && truck.cargo_amount > 0 {
+                    // Show both ore and ammo
                     painter.text(
                         Pos2::new(screen_pos.x, screen_pos.y - 4.0),
                         egui::Align2::CENTER_CENTER,
@@ -836,16 +1055,16 @@ impl eframe::App for GameApp {
                     painter.text(
                         Pos2::new(screen_pos.x, screen_pos.y + 4.0),
                         egui::Align2::CENTER_CENTER,
-                        format!("G{}", truck.bullets),
+                        format!("{}{}", weapon.short_name(), truck.ammo),
                         egui::FontId::proportional(8.0),
                         Color32::YELLOW,
                     );
-                } else if truck.has_gun {
-                    // Only show gun/bullets
+                } else if let Some(weapon) = truck.weapon {
+                    // Only show weapon/ammo
                     painter.text(
                         screen_pos,
                         egui::Align2::CENTER_CENTER,
-                        format!("G({})", truck.bullets),
+                        format!("{}({})", weapon.short_name(), truck.ammo),
                         egui::FontId::proportional(9.0),
                         Color32::WHITE,
                     );
@@ -869,34 +1088,76 @@ impl eframe::App for GameApp {
                     painter.rect_stroke(selection_rect, 0.0, (2.0, Color32::YELLOW));
                 }
                 
+                if truck.stance == Stance::HoldPosition {
+                    painter.text(
+                        Pos2::new(screen_pos.x + scaled_size / 2.0, screen_pos.y - scaled_size / 2.0),
+                        egui::Align2::LEFT_BOTTOM,
+                        "H",
+                        egui::FontId::proportional(9.0),
+                        Color32::from_rgb(255, 100, 100),
+                    );
+                }
+                
+                // Focus-fire line to the enemy being attacked
+                if let TruckOrder::Attack { enemy_id } = truck.order
+                    && let Some(enemy) = self.sim.enemies.iter().find(|e| e.id == enemy_id) {
+                    let enemy_screen = Pos2::new(enemy.position.x * self.zoom + self.camera_offset.x, enemy.position.y * self.zoom + self.camera_offset.y);
+                    painter.line_segment([screen_pos, enemy_screen], (1.0, Color32::from_rgb(255, 80, 80)));
+                    painter.circle_stroke(enemy_screen, enemy.radius() * self.zoom + 3.0, (1.5, Color32::from_rgb(255, 80, 80)));
+                }
+                
                 // Draw target indicator
                 if let Some(target) = truck.target {
                     let screen_target = Pos2::new(target.x * self.zoom + self.camera_offset.x, target.y * self.zoom + self.camera_offset.y);
                     painter.circle_stroke(screen_target, 5.0 * self.zoom, (2.0, Color32::from_rgb(255, 255, 100)));
-                    painter.line_segment(
-                        [screen_pos, screen_target],
-                        (1.0, Color32::from_rgb(150, 150, 50))
-                    );
+                    // Line follows the planned route around buildings
+                    let mut points = vec![screen_pos];
+                    points.extend(truck.route.waypoints.iter().map(|w| {
+                        Pos2::new(w.x * self.zoom + self.camera_offset.x, w.y * self.zoom + self.camera_offset.y)
+                    }));
+                    points.push(screen_target);
+                    // Attack-moves show in red
+                    let line_color = if truck.order == TruckOrder::AttackMove {
+                        Color32::from_rgb(200, 80, 80)
+                    } else {
+                        Color32::from_rgb(150, 150, 50)
+                    };
+                    painter.add(egui::Shape::line(points, (1.0, line_color)));
                 }
             }
             
             // Draw building placement preview
-            if self.build_mode != BuildMode::None {
-                if let Some(pos) = pointer_pos {
-                    let world_pos = Pos2::new(pos.x - self.camera_offset.x, pos.y - self.camera_offset.y);
-                    let screen_pos = pos;
-                    
-                    let (building_type, size) = match self.build_mode {
-                        BuildMode::PlacingGarage => (BuildingType::Garage, 40.0),
-                        BuildMode::PlacingFactory => (BuildingType::Factory, 50.0),
-                        BuildMode::None => unreachable!(),
-                    };
-                    
-                    let rect = Rect::from_center_size(screen_pos, Vec2::splat(size * 2.0));
-                    painter.rect_filled(rect, 2.0, Color32::from_rgba_premultiplied(100, 255, 100, 100));
-                    painter.rect_stroke(rect, 2.0, (2.0, Color32::GREEN));
-                }
+            if self.build_mode != BuildMode::None
+                && let Some(screen_pos) = pointer_pos {
+                let building_type = match self.build_mode {
+                    BuildMode::PlacingGarage => BuildingType::Garage,
+                    BuildMode::PlacingFactory => BuildingType::Factory,
+                    BuildMode::PlacingTurret => BuildingType::Turret,
+                    BuildMode::PlacingWall => BuildingType::Wall,
+                    BuildMode::None => unreachable!(),
+                };
+                let size = self.sim.balance.building(building_type).size;
+                
+                let rect = Rect::from_center_size(screen_pos, Vec2::splat(size * 2.0));
+                painter.rect_filled(rect, 2.0, Color32::from_rgba_premultiplied(100, 255, 100, 100));
+                painter.rect_stroke(rect, 2.0, (2.0, Color32::GREEN));
             }
         });
+        
+        if let Some(outcome) = self.sim.outcome {
+            self.show_match_summary(ctx, outcome);
+        }
+    }
+}
+
+/// How ore patches, piles and loaded trucks show each resource.
+fn resource_color(resource_type: ResourceType) -> Color32 {
+    match resource_type {
+        ResourceType::Iron => Color32::from_rgb(180, 140, 120),
+        ResourceType::Coal => Color32::from_rgb(60, 60, 70),
+        ResourceType::Copper => Color32::from_rgb(200, 120, 60),
+        ResourceType::Oil => Color32::from_rgb(55, 35, 75),
+        ResourceType::Stone => Color32::from_rgb(140, 140, 130),
+        ResourceType::Scrap => Color32::from_rgb(150, 170, 185),
     }
 }
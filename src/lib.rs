@@ -0,0 +1,17 @@
+pub mod truck;
+pub mod game;
+pub mod resource;
+pub mod building;
+pub mod enemy;
+pub mod simulation;
+pub mod save;
+pub mod balance;
+pub mod wave;
+pub mod outcome;
+pub mod replay;
+pub mod navigation;
+pub mod spatial;
+pub mod formation;
+pub mod projectile;
+pub mod weapon;
+pub mod explosion;
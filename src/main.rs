@@ -1,13 +1,16 @@
-mod truck;
-mod game;
-mod resource;
-mod building;
-mod enemy;
-
 use eframe::egui;
-use game::GameApp;
+use factory_tank_miner::balance::{self, Balance};
+use factory_tank_miner::game::GameApp;
 
 fn main() -> Result<(), eframe::Error> {
+    let balance = match Balance::load_or_default(balance::BALANCE_PATH) {
+        Ok(balance) => balance,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1280.0, 720.0]),
         ..Default::default()
@@ -16,6 +19,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Factory Tank Miner",
         options,
-        Box::new(|_cc| Ok(Box::new(GameApp::default()))),
+        Box::new(|_cc| Ok(Box::new(GameApp::new(balance)))),
     )
 }
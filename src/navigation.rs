@@ -0,0 +1,416 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use egui::Pos2;
+use serde::{Deserialize, Serialize};
+use crate::building::Building;
+
+type Cell = (i32, i32);
+
+/// Walkability of the world on a square grid, built from building
+/// footprints. The world has no edge, so only blocked cells are stored.
+#[derive(Debug, Clone, Default)]
+pub struct NavGrid {
+    blocked: HashSet<Cell>,
+    /// `Simulation::nav_revision` this grid was built from; `None` until
+    /// the first build (e.g. right after loading a save).
+    pub built_for: Option<u64>,
+}
+
+impl NavGrid {
+    pub const CELL_SIZE: f32 = 20.0;
+    /// Footprints are grown by this much so movers don't clip corners.
+    const CLEARANCE: f32 = 10.0;
+    /// Cap on A* expansions; a search that hits it gives up and the mover
+    /// heads straight for its goal instead.
+    const MAX_EXPANSIONS: usize = 4000;
+    /// How far to look for open ground around a goal inside a building.
+    const GOAL_SEARCH_RADIUS: i32 = 10;
+
+    pub fn rebuild(&mut self, buildings: &[Building], revision: u64) {
+        self.blocked.clear();
+        for building in buildings {
+            let reach = building.size + Self::CLEARANCE;
+            let min = Self::cell_at(building.position - egui::Vec2::splat(reach));
+            let max = Self::cell_at(building.position + egui::Vec2::splat(reach));
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    let center = Self::cell_center((x, y));
+                    if (center.x - building.position.x).abs() < reach
+                        && (center.y - building.position.y).abs() < reach {
+                        self.blocked.insert((x, y));
+                    }
+                }
+            }
+        }
+        self.built_for = Some(revision);
+    }
+
+    /// Waypoints leading from `start` to `goal`, not including `goal`
+    /// itself. Empty when the way is clear or no path was found, in which
+    /// case the mover should head straight for the goal.
+    pub fn find_path(&self, start: Pos2, goal: Pos2) -> Vec<Pos2> {
+        if self.line_is_clear(start, goal) {
+            return Vec::new();
+        }
+        let start_cell = Self::cell_at(start);
+        // A goal inside a building (e.g. the beacon) is approached via the
+        // nearest open cell; the last stretch is driven straight in.
+        let Some(goal_cell) = self.nearest_open_cell(Self::cell_at(goal), goal) else {
+            return Vec::new();
+        };
+        let Some(cells) = self.search(start_cell, goal_cell) else {
+            return Vec::new();
+        };
+
+        let mut waypoints: Vec<Pos2> = cells.into_iter().map(Self::cell_center).collect();
+        if !self.blocked.contains(&Self::cell_at(goal)) {
+            waypoints.pop();
+        }
+        self.smooth(start, waypoints, goal)
+    }
+
+    fn search(&self, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+        let mut cost: HashMap<Cell, u32> = HashMap::new();
+        cost.insert(start, 0);
+        open.push(OpenCell { cell: start, estimate: heuristic(start, goal), cost: 0 });
+
+        let mut expansions = 0;
+        while let Some(OpenCell { cell, cost: cell_cost, .. }) = open.pop() {
+            if cell == goal {
+                let mut path = vec![cell];
+                let mut current = cell;
+                while let Some(&previous) = came_from.get(&current) {
+                    path.push(previous);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost.get(&cell).is_some_and(|&best| cell_cost > best) {
+                continue;
+            }
+            expansions += 1;
+            if expansions > Self::MAX_EXPANSIONS {
+                return None;
+            }
+
+            for (dx, dy, step) in NEIGHBOURS {
+                let next = (cell.0 + dx, cell.1 + dy);
+                if !self.can_step(cell, dx, dy) {
+                    continue;
+                }
+                let next_cost = cell_cost + step;
+                if cost.get(&next).is_none_or(|&best| next_cost < best) {
+                    cost.insert(next, next_cost);
+                    came_from.insert(next, cell);
+                    open.push(OpenCell { cell: next, estimate: next_cost + heuristic(next, goal), cost: next_cost });
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether a mover may step from `cell` by (`dx`, `dy`). A mover inside
+    /// a footprint may always walk out of it; otherwise blocked cells and
+    /// corners between two blocked cells are off limits.
+    fn can_step(&self, cell: Cell, dx: i32, dy: i32) -> bool {
+        if self.blocked.contains(&cell) {
+            return true;
+        }
+        !self.blocked.contains(&(cell.0 + dx, cell.1 + dy)) && !self.cuts_corner(cell, dx, dy)
+    }
+
+    /// True for a diagonal step that squeezes between two blocked cells.
+    fn cuts_corner(&self, cell: Cell, dx: i32, dy: i32) -> bool {
+        dx != 0 && dy != 0
+            && (self.blocked.contains(&(cell.0 + dx, cell.1)) || self.blocked.contains(&(cell.0, cell.1 + dy)))
+    }
+
+    fn nearest_open_cell(&self, cell: Cell, goal: Pos2) -> Option<Cell> {
+        if !self.blocked.contains(&cell) {
+            return Some(cell);
+        }
+        let r = Self::GOAL_SEARCH_RADIUS;
+        (-r..=r)
+            .flat_map(|dx| (-r..=r).map(move |dy| (cell.0 + dx, cell.1 + dy)))
+            .filter(|c| !self.blocked.contains(c))
+            .min_by(|a, b| {
+                let da = (Self::cell_center(*a) - goal).length_sq();
+                let db = (Self::cell_center(*b) - goal).length_sq();
+                da.total_cmp(&db).then(a.cmp(b))
+            })
+    }
+
+    /// Drop waypoints that can be skipped with a clear straight line.
+    fn smooth(&self, start: Pos2, waypoints: Vec<Pos2>, goal: Pos2) -> Vec<Pos2> {
+        let mut points = waypoints;
+        points.push(goal);
+        let mut smoothed = Vec::new();
+        let mut from = start;
+        let mut next = 0;
+        loop {
+            let furthest = (next..points.len())
+                .rev()
+                .find(|&j| self.line_is_clear(from, points[j]))
+                .unwrap_or(next);
+            if furthest == points.len() - 1 {
+                return smoothed;
+            }
+            smoothed.push(points[furthest]);
+            from = points[furthest];
+            next = furthest + 1;
+        }
+    }
+
+    fn line_is_clear(&self, from: Pos2, to: Pos2) -> bool {
+        let steps = ((to - from).length() / (Self::CELL_SIZE * 0.5)).ceil() as usize;
+        let start_cell = Self::cell_at(from);
+        let end_cell = Self::cell_at(to);
+        (0..=steps).all(|i| {
+            let t = if steps == 0 { 0.0 } else { i as f32 / steps as f32 };
+            let cell = Self::cell_at(from + (to - from) * t);
+            // Leaving the start or entering the goal footprint is allowed
+            cell == start_cell || cell == end_cell || !self.blocked.contains(&cell)
+        })
+    }
+
+    fn cell_at(position: Pos2) -> Cell {
+        (
+            (position.x / Self::CELL_SIZE).floor() as i32,
+            (position.y / Self::CELL_SIZE).floor() as i32,
+        )
+    }
+
+    fn cell_center(cell: Cell) -> Pos2 {
+        Pos2::new(
+            (cell.0 as f32 + 0.5) * Self::CELL_SIZE,
+            (cell.1 as f32 + 0.5) * Self::CELL_SIZE,
+        )
+    }
+}
+
+/// Shared step-by-step directions to one goal, for when many movers head
+/// to the same place: a Dijkstra pass from the goal gives every cell its
+/// distance, and a mover just steps to whichever neighbour is closest.
+/// Covers a square around the goal; outside it movers head straight in.
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    origin: Cell,
+    width: i32,
+    /// Distance to the goal per cell, row-major from `origin`; `u32::MAX`
+    /// where the goal can't be reached.
+    costs: Vec<u32>,
+    pub built_for: Option<u64>,
+}
+
+impl FlowField {
+    /// Build the field toward the square footprint of half-size `goal_reach`
+    /// around `goal`, covering `radius` in every direction.
+    pub fn rebuild(&mut self, nav: &NavGrid, goal: Pos2, goal_reach: f32, radius: f32, revision: u64) {
+        let half = (radius / NavGrid::CELL_SIZE).ceil() as i32;
+        let center = NavGrid::cell_at(goal);
+        self.origin = (center.0 - half, center.1 - half);
+        self.width = half * 2 + 1;
+        self.costs = vec![u32::MAX; (self.width * self.width) as usize];
+        self.built_for = Some(revision);
+
+        let reach = goal_reach + NavGrid::CLEARANCE;
+        let is_goal = |cell: Cell| {
+            let c = NavGrid::cell_center(cell);
+            (c.x - goal.x).abs() < reach && (c.y - goal.y).abs() < reach
+        };
+
+        let mut open = BinaryHeap::new();
+        let r = (reach / NavGrid::CELL_SIZE).ceil() as i32;
+        for x in center.0 - r..=center.0 + r {
+            for y in center.1 - r..=center.1 + r {
+                if is_goal((x, y)) && let Some(idx) = self.index((x, y)) {
+                    self.costs[idx] = 0;
+                    open.push(OpenCell { cell: (x, y), estimate: 0, cost: 0 });
+                }
+            }
+        }
+
+        while let Some(OpenCell { cell, cost, .. }) = open.pop() {
+            if self.index(cell).is_some_and(|idx| cost > self.costs[idx]) {
+                continue;
+            }
+            for (dx, dy, step) in NEIGHBOURS {
+                let next = (cell.0 + dx, cell.1 + dy);
+                let Some(idx) = self.index(next) else { continue };
+                // Movers will walk this step the other way, from `next` into `cell`
+                if nav.blocked.contains(&next) || nav.cuts_corner(cell, dx, dy) {
+                    continue;
+                }
+                let next_cost = cost + step;
+                if next_cost < self.costs[idx] {
+                    self.costs[idx] = next_cost;
+                    open.push(OpenCell { cell: next, estimate: next_cost, cost: next_cost });
+                }
+            }
+        }
+    }
+
+    /// Center of the neighbouring cell one step closer to the goal, or
+    /// `None` if `position` is outside the field, already at the goal, or
+    /// cut off from it.
+    pub fn next_point(&self, nav: &NavGrid, position: Pos2) -> Option<Pos2> {
+        let cell = NavGrid::cell_at(position);
+        let cost = self.costs[self.index(cell)?];
+        if cost == 0 || cost == u32::MAX {
+            return None;
+        }
+        NEIGHBOURS.iter()
+            .filter(|(dx, dy, _)| !nav.cuts_corner(cell, *dx, *dy))
+            .filter_map(|(dx, dy, _)| {
+                let next = (cell.0 + dx, cell.1 + dy);
+                self.index(next).map(|idx| (next, self.costs[idx]))
+            })
+            // The goal's own footprint is blocked but may be stepped into
+            .filter(|(next, next_cost)| *next_cost < cost && (*next_cost == 0 || !nav.blocked.contains(next)))
+            .min_by_key(|(next, next_cost)| (*next_cost, *next))
+            .map(|(next, _)| NavGrid::cell_center(next))
+    }
+
+    fn index(&self, cell: Cell) -> Option<usize> {
+        let x = cell.0 - self.origin.0;
+        let y = cell.1 - self.origin.1;
+        if x < 0 || y < 0 || x >= self.width || y >= self.width {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+}
+
+// Straight steps cost 10, diagonals 14
+const NEIGHBOURS: [(i32, i32, u32); 8] = [
+    (1, 0, 10), (-1, 0, 10), (0, 1, 10), (0, -1, 10),
+    (1, 1, 14), (1, -1, 14), (-1, 1, 14), (-1, -1, 14),
+];
+
+fn heuristic(from: Cell, to: Cell) -> u32 {
+    let dx = (from.0 - to.0).unsigned_abs();
+    let dy = (from.1 - to.1).unsigned_abs();
+    10 * dx.max(dy) + 4 * dx.min(dy)
+}
+
+#[derive(PartialEq, Eq)]
+struct OpenCell {
+    cell: Cell,
+    estimate: u32,
+    cost: u32,
+}
+
+impl Ord for OpenCell {
+    // Reversed so the heap pops the lowest estimate; ties broken by cell
+    // so searches are deterministic
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.cmp(&self.estimate).then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A mover's planned way to its current goal. Kept with the mover (and in
+/// saves) so it is only re-planned when the goal or the buildings change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Route {
+    pub waypoints: Vec<Pos2>,
+    pub goal: Option<Pos2>,
+    pub revision: u64,
+}
+
+impl Route {
+    /// Re-plan if the goal has moved more than `tolerance` or the buildings
+    /// have changed since the last plan.
+    pub fn plan(&mut self, nav: &NavGrid, from: Pos2, goal: Pos2, revision: u64, tolerance: f32) {
+        let goal_moved = self.goal.is_none_or(|g| (g - goal).length() > tolerance);
+        if !goal_moved && self.revision == revision {
+            return;
+        }
+        self.waypoints = nav.find_path(from, goal);
+        self.goal = Some(goal);
+        self.revision = revision;
+    }
+
+    pub fn clear(&mut self) {
+        self.waypoints.clear();
+        self.goal = None;
+    }
+
+    /// Where to head next: the first waypoint, or the goal once they are used up.
+    pub fn next_point(&self, goal: Pos2) -> Pos2 {
+        self.waypoints.first().copied().unwrap_or(goal)
+    }
+
+    /// Call after moving; drops the first waypoint once it has been reached.
+    pub fn advance(&mut self, position: Pos2) {
+        if self.waypoints.first().is_some_and(|w| (*w - position).length() < 2.0) {
+            self.waypoints.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance::Balance;
+    use crate::building::{wall_segments, BuildingType};
+
+    fn wall(start: Pos2, end: Pos2) -> Vec<Building> {
+        let balance = Balance::default();
+        wall_segments(start, end, balance.buildings.wall.size).into_iter()
+            .map(|position| Building::new(position, BuildingType::Wall, &balance))
+            .collect()
+    }
+
+    #[test]
+    fn open_ground_needs_no_waypoints() {
+        let mut nav = NavGrid::default();
+        nav.rebuild(&wall(Pos2::new(100.0, 100.0), Pos2::new(200.0, 100.0)), 0);
+        assert!(nav.find_path(Pos2::new(0.0, 0.0), Pos2::new(300.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn paths_go_around_buildings_in_the_way() {
+        let mut nav = NavGrid::default();
+        nav.rebuild(&wall(Pos2::new(100.0, -200.0), Pos2::new(100.0, 200.0)), 0);
+        let (start, goal) = (Pos2::new(0.0, 0.0), Pos2::new(200.0, 0.0));
+
+        let waypoints = nav.find_path(start, goal);
+        assert!(!waypoints.is_empty());
+        let legs = std::iter::once(start).chain(waypoints.iter().copied()).chain(std::iter::once(goal));
+        let points: Vec<Pos2> = legs.collect();
+        for leg in points.windows(2) {
+            assert!(nav.line_is_clear(leg[0], leg[1]), "{:?} cuts through the wall", leg);
+        }
+    }
+
+    #[test]
+    fn the_flow_field_leads_around_walls_to_the_goal() {
+        let balance = Balance::default();
+        let beacon = Building::new(Pos2::ZERO, BuildingType::Beacon, &balance);
+        let mut buildings = wall(Pos2::new(150.0, -150.0), Pos2::new(150.0, 150.0));
+        buildings.push(beacon.clone());
+        let mut nav = NavGrid::default();
+        nav.rebuild(&buildings, 0);
+        let mut flow = FlowField::default();
+        flow.rebuild(&nav, beacon.position, beacon.size, 400.0, 0);
+
+        let mut position = Pos2::new(300.0, 0.0);
+        for _ in 0..100 {
+            let Some(next) = flow.next_point(&nav, position) else { break };
+            let cell = NavGrid::cell_at(next);
+            assert!(!nav.blocked.contains(&cell) || flow.costs[flow.index(cell).unwrap()] == 0, "stepped into the wall at {:?}", next);
+            position = next;
+        }
+        let reach = beacon.size + NavGrid::CLEARANCE;
+        assert!((position.x - beacon.position.x).abs() < reach && (position.y - beacon.position.y).abs() < reach);
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::resource::Inventory;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MatchOutcome {
+    Victory,
+    Defeat,
+}
+
+/// Optional win conditions; a match with none set is endless.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VictoryConditions {
+    /// Win once this many waves have been survived (see `Simulation::waves_survived`).
+    pub survive_waves: Option<u32>,
+    /// Win once the beacon holds this much iron.
+    pub stockpile_iron: Option<u32>,
+}
+
+/// Running totals shown on the end-of-match screen.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatchStats {
+    pub enemies_killed: u32,
+    /// Everything trucks have unloaded at the beacon, spent or not.
+    pub collected: Inventory,
+    pub trucks_built: u32,
+    pub trucks_lost: u32,
+    pub buildings_lost: u32,
+}
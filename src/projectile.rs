@@ -0,0 +1,94 @@
+use egui::{Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+use crate::enemy::Enemy;
+use crate::spatial::SpatialGrid;
+
+/// A shot in flight. It flies straight at where its target stood when it
+/// was fired and hits the first enemy in its path, so a target that has
+/// moved on by the time it arrives is missed. A shot with a splash radius
+/// instead bursts on the first enemy it meets or where it was aimed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projectile {
+    pub position: Pos2,
+    pub velocity: Vec2,
+    pub damage: u32,
+    /// Burst radius on impact; 0 for a shot that hits one enemy.
+    pub splash_radius: f32,
+    /// Share of the damage lost toward the edge of the burst.
+    pub splash_falloff: f32,
+    /// Distance left before the shot is spent.
+    pub range_left: f32,
+}
+
+impl Projectile {
+    /// How far past its aim point a shot keeps flying before it is spent.
+    pub const OVERSHOOT: f32 = 30.0;
+
+    pub fn new(from: Pos2, aim: Pos2, speed: f32, damage: u32) -> Self {
+        let offset = aim - from;
+        let direction = if offset.length_sq() > 0.0 { offset.normalized() } else { Vec2::X };
+        Self {
+            position: from,
+            velocity: direction * speed,
+            damage,
+            splash_radius: 0.0,
+            splash_falloff: 0.0,
+            range_left: offset.length() + Self::OVERSHOOT,
+        }
+    }
+
+    /// Make the shot burst over `radius` instead of hitting one enemy. It
+    /// comes down on its aim point rather than flying past it.
+    pub fn with_splash(mut self, radius: f32, falloff: f32) -> Self {
+        if radius > 0.0 {
+            self.splash_radius = radius;
+            self.splash_falloff = falloff;
+            self.range_left -= Self::OVERSHOOT;
+        }
+        self
+    }
+
+    /// Fly one tick. Returns the index of the enemy hit along the way, if
+    /// any; the caller applies the damage and drops the projectile.
+    pub fn advance(&mut self, delta_time: f32, enemies: &[Enemy], grid: &SpatialGrid) -> Option<usize> {
+        let step = (self.velocity.length() * delta_time).min(self.range_left);
+        let direction = self.velocity.normalized();
+        let start = self.position;
+
+        // Closest hit along the swept segment, ties broken by index
+        let hit = grid.query(start + direction * step / 2.0, step / 2.0)
+            .filter(|&idx| enemies[idx].health > 0)
+            .filter_map(|idx| {
+                let to_enemy = enemies[idx].position - start;
+                let along = to_enemy.dot(direction).clamp(0.0, step);
+                let miss = (to_enemy - direction * along).length();
+                (miss <= enemies[idx].radius()).then_some((idx, along))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        match hit {
+            Some((idx, along)) => {
+                self.position = start + direction * along;
+                self.range_left = 0.0;
+                Some(idx)
+            }
+            None => {
+                self.position = start + direction * step;
+                self.range_left -= step;
+                None
+            }
+        }
+    }
+
+    /// Fly one tick without hitting anything on the way, for shots lobbed
+    /// over everything onto their aim point.
+    pub fn fly(&mut self, delta_time: f32) {
+        let step = (self.velocity.length() * delta_time).min(self.range_left);
+        self.position += self.velocity.normalized() * step;
+        self.range_left -= step;
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.range_left <= 0.0
+    }
+}
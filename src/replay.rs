@@ -0,0 +1,130 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::save::{self, SaveError};
+use crate::simulation::{Command, Simulation};
+use crate::balance::Balance;
+
+/// Bump whenever `Command` or the simulation rules change in a way that
+/// would make old replays play out differently.
+pub const REPLAY_VERSION: u32 = 28;
+
+pub const REPLAY_PATH: &str = "replay.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    /// Number of ticks the simulation had run when the command was issued.
+    pub tick: u64,
+    pub command: Command,
+}
+
+/// Everything needed to reproduce a match: the seed and balance it started
+/// from and every player command in the order it was applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub balance: Balance,
+    pub commands: Vec<RecordedCommand>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, balance: Balance) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            balance,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, command: Command) {
+        self.commands.push(RecordedCommand { tick, command });
+    }
+
+    /// Tick of the last recorded command, i.e. where the interesting part ends.
+    pub fn last_tick(&self) -> u64 {
+        self.commands.last().map(|c| c.tick).unwrap_or(0)
+    }
+
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        save::write_ron(self, path)
+    }
+
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        save::read_versioned_ron(path, REPLAY_VERSION)
+    }
+}
+
+/// Feeds a replay's commands back into a simulation at the ticks they were
+/// recorded on.
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_command: usize,
+}
+
+impl ReplayPlayer {
+    /// Start playback, returning the fresh simulation it drives.
+    pub fn start(replay: Replay) -> (Self, Simulation) {
+        let sim = Simulation::new(replay.seed, replay.balance.clone());
+        (Self { replay, next_command: 0 }, sim)
+    }
+
+    /// Apply every command due before the simulation's next step.
+    pub fn apply_due(&mut self, sim: &mut Simulation) {
+        while let Some(recorded) = self.replay.commands.get(self.next_command) {
+            if recorded.tick > sim.tick {
+                break;
+            }
+            sim.apply(&recorded.command);
+            self.next_command += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_command >= self.replay.commands.len()
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Pos2;
+    use crate::formation::Formation;
+
+    #[test]
+    fn a_replay_plays_back_the_recorded_match() {
+        let mut replay = Replay::new(11, Balance { grace_period: 5.0, ..Balance::default() });
+        let mut sim = Simulation::new(replay.seed, replay.balance.clone());
+        let orders = [
+            (20, Command::SelectTrucks { truck_ids: vec![0, 1], toggle: false }),
+            (20, Command::MoveSelected { target: Pos2::new(150.0, 100.0), formation: Formation::default(), facing: None, attack: false }),
+            (400, Command::SelectTrucks { truck_ids: vec![2], toggle: false }),
+            (400, Command::MoveSelected { target: Pos2::new(-150.0, 100.0), formation: Formation::default(), facing: None, attack: false }),
+        ];
+        for tick in 0..1200 {
+            for (_, command) in orders.iter().filter(|(at, _)| *at == tick) {
+                if sim.apply(command) {
+                    replay.record(sim.tick, command.clone());
+                }
+            }
+            sim.step();
+        }
+
+        let path = std::env::temp_dir().join(format!("factory-tank-miner-replay-{}.ron", std::process::id()));
+        replay.save_to_file(&path).unwrap();
+        let loaded = Replay::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (mut player, mut played) = ReplayPlayer::start(loaded);
+        while played.tick < sim.tick {
+            player.apply_due(&mut played);
+            played.step();
+        }
+        assert!(player.is_finished());
+        assert_eq!(ron::to_string(&sim).unwrap(), ron::to_string(&played).unwrap());
+    }
+}
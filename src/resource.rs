@@ -1,26 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use egui::Pos2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ResourceType {
     Iron,
     Coal,
+    Copper,
+    Oil,
+    Stone,
+    /// Salvaged from dead enemies rather than mined.
+    Scrap,
 }
 
-#[derive(Debug, Clone)]
+impl ResourceType {
+    pub const ALL: [ResourceType; 6] = [
+        ResourceType::Iron,
+        ResourceType::Coal,
+        ResourceType::Copper,
+        ResourceType::Oil,
+        ResourceType::Stone,
+        ResourceType::Scrap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResourceType::Iron => "Iron",
+            ResourceType::Coal => "Coal",
+            ResourceType::Copper => "Copper",
+            ResourceType::Oil => "Oil",
+            ResourceType::Stone => "Stone",
+            ResourceType::Scrap => "Scrap",
+        }
+    }
+
+    /// Short form used in prices.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ResourceType::Iron => "Fe",
+            ResourceType::Coal => "C",
+            ResourceType::Copper => "Cu",
+            ResourceType::Oil => "Oil",
+            ResourceType::Stone => "St",
+            ResourceType::Scrap => "Sc",
+        }
+    }
+
+    /// Whether it comes out of ore patches.
+    pub fn is_mined(self) -> bool {
+        self != ResourceType::Scrap
+    }
+}
+
+/// An amount of each kind of resource, e.g. the beacon's stockpile. Kinds
+/// it doesn't list count as none.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory(BTreeMap<ResourceType, u32>);
+
+/// Price of a building or production item.
+pub type Cost = Inventory;
+
+impl Inventory {
+    pub fn get(&self, resource_type: ResourceType) -> u32 {
+        self.0.get(&resource_type).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, resource_type: ResourceType, amount: u32) {
+        if amount > 0 {
+            *self.0.entry(resource_type).or_default() += amount;
+        }
+    }
+
+    /// Whether this holds at least as much of everything as `other`.
+    pub fn covers(&self, other: &Inventory) -> bool {
+        other.iter().all(|(resource_type, amount)| self.get(resource_type) >= amount)
+    }
+
+    /// Take `other` out of this. Takes nothing and returns false if this
+    /// doesn't cover it.
+    pub fn take(&mut self, other: &Inventory) -> bool {
+        if !self.covers(other) {
+            return false;
+        }
+        for (resource_type, amount) in other.iter() {
+            if let Some(held) = self.0.get_mut(&resource_type) {
+                *held -= amount;
+            }
+        }
+        // Keep emptied kinds out of the map so equal holdings compare equal
+        self.0.retain(|_, held| *held > 0);
+        true
+    }
+
+    /// Kinds with a non-zero amount, in `ResourceType` order.
+    pub fn iter(&self) -> impl Iterator<Item = (ResourceType, u32)> + '_ {
+        self.0.iter().filter(|(_, amount)| **amount > 0).map(|(r, a)| (*r, *a))
+    }
+}
+
+impl<const N: usize> From<[(ResourceType, u32); N]> for Inventory {
+    fn from(amounts: [(ResourceType, u32); N]) -> Self {
+        let mut inventory = Inventory::default();
+        for (resource_type, amount) in amounts {
+            inventory.add(resource_type, amount);
+        }
+        inventory
+    }
+}
+
+impl std::ops::Mul<u32> for &Inventory {
+    type Output = Inventory;
+
+    fn mul(self, count: u32) -> Inventory {
+        let mut product = Inventory::default();
+        for (resource_type, amount) in self.iter() {
+            product.add(resource_type, amount * count);
+        }
+        product
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (resource_type, amount) in self.iter() {
+            if !first {
+                write!(f, " ")?;
+            }
+            write!(f, "{}{}", amount, resource_type.symbol())?;
+            first = false;
+        }
+        if first {
+            write!(f, "free")?;
+        }
+        Ok(())
+    }
+}
+
+/// A mineable deposit. Patches are never removed once depleted, so
+/// indices into `Simulation::ore_patches` stay valid for the whole match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrePatch {
     pub position: Pos2,
     pub size: f32,
     pub resource_type: ResourceType,
-    pub amount: u32, // Infinite for now, but could be depleted later
+    pub amount: u32,
+    pub initial_amount: u32,
 }
 
 impl OrePatch {
-    pub fn new(position: Pos2, resource_type: ResourceType) -> Self {
+    pub fn new(position: Pos2, resource_type: ResourceType, amount: u32) -> Self {
         Self {
             position,
             size: 40.0,
             resource_type,
-            amount: 999999, // Effectively infinite
+            amount,
+            initial_amount: amount,
         }
     }
     
@@ -29,4 +166,112 @@ impl OrePatch {
         let dy = point.y - self.position.y;
         (dx * dx + dy * dy) < (self.size * self.size)
     }
+    
+    pub fn is_depleted(&self) -> bool {
+        self.amount == 0
+    }
+    
+    /// Share of the original ore still left, from 1.0 down to 0.0.
+    pub fn remaining_fraction(&self) -> f32 {
+        if self.initial_amount == 0 {
+            0.0
+        } else {
+            self.amount as f32 / self.initial_amount as f32
+        }
+    }
+}
+
+/// Cargo spilled by a destroyed truck or scrap dropped by a dead enemy,
+/// waiting for a truck to drive over it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourcePile {
+    pub position: Pos2,
+    pub resource_type: ResourceType,
+    pub amount: u32,
+}
+
+impl ResourcePile {
+    pub const PICKUP_RADIUS: f32 = 20.0;
+
+    pub fn new(position: Pos2, resource_type: ResourceType, amount: u32) -> Self {
+        Self { position, resource_type, amount }
+    }
+}
+
+/// Leave `amount` of `resource_type` at `position`, topping up a pile of
+/// the same kind already within reach instead of starting a new one, so
+/// drops in a busy spot don't pile up without bound.
+pub fn drop_resource(piles: &mut Vec<ResourcePile>, position: Pos2, resource_type: ResourceType, amount: u32) {
+    if amount == 0 {
+        return;
+    }
+    let existing = piles.iter_mut().find(|p| {
+        p.resource_type == resource_type && (p.position - position).length() <= ResourcePile::PICKUP_RADIUS
+    });
+    match existing {
+        Some(pile) => pile.amount += amount,
+        None => piles.push(ResourcePile::new(position, resource_type, amount)),
+    }
+}
+
+/// Index of the non-depleted patch of `resource_type` closest to `position`.
+pub fn nearest_patch(patches: &[OrePatch], position: Pos2, resource_type: ResourceType) -> Option<usize> {
+    patches.iter()
+        .enumerate()
+        .filter(|(_, p)| p.resource_type == resource_type && !p.is_depleted())
+        .min_by(|(_, a), (_, b)| {
+            let da = (a.position - position).length_sq();
+            let db = (b.position - position).length_sq();
+            da.total_cmp(&db)
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Index of the scrap pile closest to `position`.
+pub fn nearest_scrap(piles: &[ResourcePile], position: Pos2) -> Option<usize> {
+    piles.iter()
+        .enumerate()
+        .filter(|(_, p)| p.resource_type == ResourceType::Scrap && p.amount > 0)
+        .min_by(|(_, a), (_, b)| {
+            let da = (a.position - position).length_sq();
+            let db = (b.position - position).length_sq();
+            da.total_cmp(&db)
+        })
+        .map(|(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ResourceType::{Coal, Copper, Iron, Scrap};
+
+    #[test]
+    fn covers_needs_enough_of_every_kind() {
+        let stock = Inventory::from([(Iron, 50), (Coal, 10)]);
+        assert!(stock.covers(&Inventory::from([(Iron, 50), (Coal, 10)])));
+        assert!(stock.covers(&Inventory::default()));
+        assert!(!stock.covers(&Inventory::from([(Iron, 51)])));
+        assert!(!stock.covers(&Inventory::from([(Iron, 10), (Copper, 1)])));
+    }
+
+    #[test]
+    fn take_is_all_or_nothing() {
+        let mut stock = Inventory::from([(Iron, 50), (Coal, 10)]);
+        assert!(stock.take(&Inventory::from([(Iron, 20), (Coal, 10)])));
+        assert_eq!(stock, Inventory::from([(Iron, 30)]));
+
+        assert!(!stock.take(&Inventory::from([(Iron, 10), (Scrap, 1)])));
+        assert_eq!(stock.get(Iron), 30);
+    }
+
+    #[test]
+    fn drops_merge_into_a_nearby_pile_of_the_same_kind() {
+        let mut piles = Vec::new();
+        drop_resource(&mut piles, Pos2::new(0.0, 0.0), Scrap, 5);
+        drop_resource(&mut piles, Pos2::new(10.0, 0.0), Scrap, 2);
+        drop_resource(&mut piles, Pos2::new(10.0, 0.0), Iron, 3);
+        drop_resource(&mut piles, Pos2::new(100.0, 0.0), Scrap, 4);
+        let amounts: Vec<_> = piles.iter().map(|p| (p.resource_type, p.amount)).collect();
+        assert_eq!(amounts, vec![(Scrap, 7), (Iron, 3), (Scrap, 4)]);
+    }
 }
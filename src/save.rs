@@ -0,0 +1,147 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::simulation::Simulation;
+
+/// Bump whenever the shape of `Simulation` changes so old files are
+/// rejected with a clear message instead of a confusing parse error.
+pub const SAVE_VERSION: u32 = 28;
+
+/// Where the menu buttons and quick-save hotkeys read and write.
+pub const SAVE_PATH: &str = "savegame.ron";
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    simulation: &'a Simulation,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    simulation: Simulation,
+}
+
+/// Only the version, so it can be checked before the rest is parsed.
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Parse(ron::error::SpannedError),
+    UnsupportedVersion { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access file: {}", err),
+            SaveError::Serialize(err) => write!(f, "could not write data: {}", err),
+            SaveError::Parse(err) => write!(f, "file is corrupt: {}", err),
+            SaveError::UnsupportedVersion { found, expected } => write!(
+                f,
+                "file version {} is not supported (expected {})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        SaveError::Serialize(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SaveError::Parse(err)
+    }
+}
+
+pub fn save_to_file(simulation: &Simulation, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    write_ron(&SaveFileRef { version: SAVE_VERSION, simulation }, path)
+}
+
+pub fn load_from_file(path: impl AsRef<Path>) -> Result<Simulation, SaveError> {
+    let file: SaveFile = read_versioned_ron(path, SAVE_VERSION)?;
+    Ok(file.simulation)
+}
+
+pub(crate) fn write_ron<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Read a RON file whose top-level struct carries a `version` field,
+/// rejecting it before full parsing if the version doesn't match.
+pub(crate) fn read_versioned_ron<T: DeserializeOwned>(path: impl AsRef<Path>, expected: u32) -> Result<T, SaveError> {
+    let text = fs::read_to_string(path)?;
+
+    let header: VersionHeader = ron::from_str(&text)?;
+    if header.version != expected {
+        return Err(SaveError::UnsupportedVersion { found: header.version, expected });
+    }
+
+    Ok(ron::from_str(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Pos2;
+    use crate::balance::Balance;
+    use crate::formation::Formation;
+    use crate::simulation::Command;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("factory-tank-miner-{}-{}.ron", name, std::process::id()))
+    }
+
+    #[test]
+    fn a_loaded_save_carries_on_exactly_like_the_original() {
+        let mut sim = Simulation::new(7, Balance { grace_period: 5.0, ..Balance::default() });
+        sim.apply(&Command::SelectTrucks { truck_ids: vec![1, 2], toggle: false });
+        sim.apply(&Command::MoveSelected { target: Pos2::new(-150.0, 100.0), formation: Formation::default(), facing: None, attack: false });
+        for _ in 0..600 {
+            sim.step();
+        }
+
+        let path = temp_path("save");
+        save_to_file(&sim, &path).unwrap();
+        let mut loaded = load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        for _ in 0..600 {
+            sim.step();
+            loaded.step();
+        }
+        assert_eq!(ron::to_string(&sim).unwrap(), ron::to_string(&loaded).unwrap());
+    }
+
+    #[test]
+    fn saves_from_another_version_are_rejected() {
+        let sim = Simulation::new(7, Balance::default());
+        let path = temp_path("old-save");
+        write_ron(&SaveFileRef { version: SAVE_VERSION - 1, simulation: &sim }, &path).unwrap();
+        let result = load_from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(SaveError::UnsupportedVersion { found, expected })
+            if found == SAVE_VERSION - 1 && expected == SAVE_VERSION));
+    }
+}
@@ -0,0 +1,1410 @@
+use egui::{Pos2, Vec2};
+use crate::truck::{Stance, Truck, TruckOrder, TruckState};
+use crate::resource::{drop_resource, nearest_patch, nearest_scrap, Inventory, OrePatch, ResourcePile, ResourceType};
+use crate::building::{wall_segments, Building, BuildingType, ProductionType};
+use crate::enemy::{BossPhase, Enemy, EnemyBehavior, EnemySize, Goal, Senses};
+use crate::projectile::Projectile;
+use crate::explosion::{falloff_damage, Explosion};
+use crate::weapon::{AmmoType, WeaponType};
+use crate::balance::{Balance, BlastStats, EnemyAbility, EnemyStats};
+use crate::resource::Cost;
+use crate::wave::WaveDirector;
+use crate::navigation::{FlowField, NavGrid};
+use crate::spatial::SpatialIndex;
+use crate::formation::{cluster_slots, Formation};
+use crate::outcome::{MatchOutcome, MatchStats};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// A player order. Every change the player makes to the world goes through
+/// `Simulation::apply`, which is what lets replays re-drive a match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    /// Select exactly these trucks, or flip their selection when `toggle` is set.
+    SelectTrucks { truck_ids: Vec<usize>, toggle: bool },
+    /// Send every selected truck to `target` in `formation`, looking along
+    /// `facing`, or away from the group when it is `None`. With `attack`,
+    /// armed trucks stop to fight anything they meet on the way.
+    MoveSelected { target: Pos2, formation: Formation, facing: Option<Vec2>, attack: bool },
+    /// Have the selected armed trucks chase and focus fire on one enemy.
+    AttackEnemy { enemy_id: usize },
+    SetStance { stance: Stance },
+    PlaceBuilding { building_type: BuildingType, position: Pos2 },
+    /// Lay a line of wall segments from `start` to `end`, as many as can be afforded.
+    PlaceWall { start: Pos2, end: Pos2 },
+    QueueProduction { building_idx: usize, production_type: ProductionType },
+}
+
+/// Headless game state and rules. Owns every entity and stockpile and
+/// advances them one fixed tick at a time with `step`; it knows nothing
+/// about rendering or input. Two simulations built from the same seed and
+/// fed the same commands on the same ticks stay identical.
+#[derive(Serialize, Deserialize)]
+pub struct Simulation {
+    pub seed: u64,
+    pub balance: Balance,
+    pub tick: u64,
+    pub trucks: Vec<Truck>,
+    pub ore_patches: Vec<OrePatch>,
+    pub resource_piles: Vec<ResourcePile>,
+    pub buildings: Vec<Building>,
+    pub enemies: Vec<Enemy>,
+    /// Shots in flight from trucks and turrets.
+    pub projectiles: Vec<Projectile>,
+    /// Acid lobbed by spitters, in flight.
+    pub spit: Vec<Projectile>,
+    /// Recent blasts, kept only to be drawn.
+    pub explosions: Vec<Explosion>,
+    pub next_truck_id: usize,
+    pub next_enemy_id: usize,
+    /// Everything unloaded at the beacon and not yet spent.
+    pub stockpile: Inventory,
+    pub waves: WaveDirector,
+    pub game_timer: f32,
+    /// Seconds until another ore field is discovered.
+    pub ore_discovery_timer: f32,
+    /// Ore fields discovered so far; each lies further out than the last.
+    pub ore_discoveries: u32,
+    /// Set once the match is won or lost; the simulation stops advancing.
+    pub outcome: Option<MatchOutcome>,
+    pub stats: MatchStats,
+    /// Bumped whenever a building is placed or destroyed, so the nav grid
+    /// and any routes planned on it know to refresh.
+    pub nav_revision: u64,
+    /// Rebuilt from `buildings` on demand rather than saved.
+    #[serde(skip)]
+    nav: NavGrid,
+    /// Shared directions to the beacon for every enemy not chasing a truck.
+    #[serde(skip)]
+    flow: FlowField,
+    /// Grids for range queries, refreshed as entities move.
+    #[serde(skip)]
+    index: SpatialIndex,
+    rng: ChaCha8Rng,
+}
+
+impl Simulation {
+    /// Simulation ticks per second of game time.
+    pub const TICK_RATE: u32 = 60;
+    /// Length of one tick in seconds.
+    pub const TICK_DT: f32 = 1.0 / Self::TICK_RATE as f32;
+    pub const BEACON_POS: Pos2 = Pos2::new(0.0, 0.0);
+    /// Enemies chasing a moving truck re-plan once it has moved this far.
+    const ENEMY_REPLAN_DISTANCE: f32 = 40.0;
+    /// Gap between truck slots when a group arrives together.
+    const FORMATION_SPACING: f32 = 26.0;
+    /// Trucks this close to the beacon unload.
+    const UNLOAD_RANGE: f32 = 35.0;
+    /// Trucks this close to a factory pick up weapons and ammo.
+    const EQUIP_RANGE: f32 = 70.0;
+    /// The flow field reaches this far past where enemies spawn.
+    const FLOW_FIELD_MARGIN: f32 = 500.0;
+
+    pub fn new(seed: u64, balance: Balance) -> Self {
+        let truck_stats = &balance.truck;
+        let mut armed_truck = Truck::new(0, Pos2::new(50.0, 50.0), truck_stats);
+        armed_truck.weapon = Some(WeaponType::MachineGun);
+        armed_truck.ammo = 200;
+        let trucks = vec![
+            armed_truck,
+            Truck::new(1, Pos2::new(100.0, 50.0), truck_stats),
+            Truck::new(2, Pos2::new(75.0, 100.0), truck_stats),
+        ];
+
+        let ore = &balance.ore;
+        let ore_patches = vec![
+            OrePatch::new(Pos2::new(-150.0, 100.0), ResourceType::Iron, ore.starting_amount),
+            OrePatch::new(Pos2::new(150.0, 100.0), ResourceType::Coal, ore.starting_amount),
+        ];
+        let (initial_fields, field_min, field_max) = (ore.initial_fields, ore.field_distance_min, ore.field_distance_max);
+        let discovery_interval = ore.discovery_interval;
+
+        let buildings = vec![Building::new(Self::BEACON_POS, BuildingType::Beacon, &balance)];
+        let grace_period = balance.grace_period;
+
+        let mut sim = Self {
+            seed,
+            balance,
+            tick: 0,
+            trucks,
+            ore_patches,
+            resource_piles: Vec::new(),
+            buildings,
+            enemies: Vec::new(),
+            projectiles: Vec::new(),
+            spit: Vec::new(),
+            explosions: Vec::new(),
+            next_truck_id: 3,
+            next_enemy_id: 0,
+            stockpile: Inventory::default(),
+            waves: WaveDirector::new(grace_period),
+            game_timer: 0.0,
+            ore_discovery_timer: discovery_interval,
+            ore_discoveries: 0,
+            outcome: None,
+            stats: MatchStats::default(),
+            nav_revision: 0,
+            nav: NavGrid::default(),
+            flow: FlowField::default(),
+            index: SpatialIndex::default(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        };
+        // Every ore gets at least one field, then the rest are left to chance
+        let missing: Vec<ResourceType> = sim.balance.ore.weights.iter()
+            .filter(|(r, w)| **w > 0 && !sim.ore_patches.iter().any(|p| p.resource_type == **r))
+            .map(|(r, _)| *r)
+            .collect();
+        for i in 0..initial_fields as usize {
+            let resource_type = match missing.get(i) {
+                Some(resource_type) => *resource_type,
+                None => sim.random_ore(),
+            };
+            sim.generate_ore_patch(resource_type, field_min, field_max);
+        }
+        sim
+    }
+
+    /// Advance the whole game by one fixed tick. Does nothing once the
+    /// match is over.
+    pub fn step(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let delta_time = Self::TICK_DT;
+        self.tick += 1;
+        self.game_timer += delta_time;
+
+        self.spawn_enemies(delta_time);
+        self.discover_ore(delta_time);
+        self.update_enemies(delta_time);
+        self.update_bosses(delta_time);
+        self.update_trucks(delta_time);
+        self.update_turrets(delta_time);
+        self.fade_explosions(delta_time);
+        self.process_attacks();
+        self.update_projectiles(delta_time);
+        self.update_spit(delta_time);
+        self.update_production(delta_time);
+        self.check_outcome();
+    }
+
+    /// Waves the beacon has outlasted: every wave before the current one,
+    /// plus the current one once it has finished spawning and been wiped out.
+    pub fn waves_survived(&self) -> u32 {
+        if self.waves.is_spawning() || !self.enemies.is_empty() {
+            self.waves.wave_number.saturating_sub(1)
+        } else {
+            self.waves.wave_number
+        }
+    }
+
+    fn check_outcome(&mut self) {
+        if !self.buildings.iter().any(|b| b.building_type == BuildingType::Beacon) {
+            self.outcome = Some(MatchOutcome::Defeat);
+            return;
+        }
+
+        let victory = &self.balance.victory;
+        let survived = victory.survive_waves.is_some_and(|n| self.waves_survived() >= n);
+        let stockpiled = victory.stockpile_iron.is_some_and(|n| self.stockpile.get(ResourceType::Iron) >= n);
+        if survived || stockpiled {
+            self.outcome = Some(MatchOutcome::Victory);
+        }
+    }
+
+    /// Carry out a player command. Returns false if it had no effect
+    /// (e.g. the player couldn't afford it).
+    pub fn apply(&mut self, command: &Command) -> bool {
+        if self.outcome.is_some() {
+            return false;
+        }
+        match command {
+            Command::SelectTrucks { truck_ids, toggle } => {
+                for truck in &mut self.trucks {
+                    let listed = truck_ids.contains(&truck.id);
+                    if *toggle {
+                        truck.selected ^= listed;
+                    } else {
+                        truck.selected = listed;
+                    }
+                }
+                true
+            }
+            Command::MoveSelected { target, formation, facing, attack } => {
+                // Unarmed trucks sent onto scrap go salvaging instead of forming up
+                let scrap = nearest_scrap(&self.resource_piles, *target)
+                    .map(|i| self.resource_piles[i].position)
+                    .filter(|pile| (*pile - *target).length() <= ResourcePile::PICKUP_RADIUS);
+                for (i, slot) in self.formation_slots(*target, *formation, *facing) {
+                    let truck = &mut self.trucks[i];
+                    // A new move order releases a held position
+                    truck.stance = Stance::Free;
+                    if let Some(pile) = scrap
+                        && truck.weapon.is_none() {
+                        truck.start_moving(pile);
+                        // A truck on its way home starts once it has unloaded
+                        truck.salvaging = true;
+                        continue;
+                    }
+                    truck.start_moving(slot);
+                    if *attack && truck.weapon.is_some() && truck.state == TruckState::Moving {
+                        truck.order = TruckOrder::AttackMove;
+                    }
+                }
+                true
+            }
+            Command::AttackEnemy { enemy_id } => {
+                if !self.enemies.iter().any(|e| e.id == *enemy_id && !e.burrowed) {
+                    return false;
+                }
+                let mut ordered = false;
+                for truck in self.trucks.iter_mut().filter(|t| t.selected && t.weapon.is_some()) {
+                    if truck.state != TruckState::ReturningToBase {
+                        truck.order = TruckOrder::Attack { enemy_id: *enemy_id };
+                        ordered = true;
+                    }
+                }
+                ordered
+            }
+            Command::SetStance { stance } => {
+                let mut changed = false;
+                for truck in self.trucks.iter_mut().filter(|t| t.selected) {
+                    truck.stance = *stance;
+                    if *stance == Stance::HoldPosition {
+                        truck.route.clear();
+                    }
+                    changed = true;
+                }
+                changed
+            }
+            Command::PlaceBuilding { building_type, position } => {
+                self.place_building(*building_type, *position)
+            }
+            Command::PlaceWall { start, end } => {
+                let size = self.balance.building(BuildingType::Wall).size;
+                let mut placed = false;
+                for position in wall_segments(*start, *end, size) {
+                    if !self.place_building(BuildingType::Wall, position) {
+                        break;
+                    }
+                    placed = true;
+                }
+                placed
+            }
+            Command::QueueProduction { building_idx, production_type } => {
+                self.queue_production(*building_idx, *production_type)
+            }
+        }
+    }
+
+    /// Index of the enemy closest to `position` within `range`, if any.
+    pub fn closest_enemy_in_range(&self, position: Pos2, range: f32) -> Option<usize> {
+        self.index.closest_enemy(&self.enemies, position, range)
+    }
+
+    /// Index of the enemy `truck` fires at: its attack target while that is
+    /// in range, otherwise the closest enemy unless it is busy chasing.
+    fn truck_firing_target(&self, truck: &Truck) -> Option<usize> {
+        let range = self.balance.weapon(truck.weapon?).range;
+        if let TruckOrder::Attack { enemy_id } = truck.order
+            && let Some(idx) = self.enemies.iter().position(|e| e.id == enemy_id) {
+            let in_range = (self.enemies[idx].position - truck.position).length() <= range;
+            // A holding truck can't chase, so it fights whatever it can reach meanwhile
+            return if in_range {
+                Some(idx)
+            } else if truck.stance == Stance::HoldPosition {
+                self.closest_enemy_in_range(truck.position, range)
+            } else {
+                None
+            };
+        }
+        self.closest_enemy_in_range(truck.position, range)
+    }
+
+    pub fn can_afford(&self, cost: &Cost) -> bool {
+        self.stockpile.covers(cost)
+    }
+
+    fn spend(&mut self, cost: &Cost) -> bool {
+        self.stockpile.take(cost)
+    }
+
+    /// Pay for and place a new building. Returns false if it can't be afforded.
+    fn place_building(&mut self, building_type: BuildingType, position: Pos2) -> bool {
+        let cost = self.balance.building(building_type).cost.clone();
+        if !self.spend(&cost) {
+            return false;
+        }
+        self.buildings.push(Building::new(position, building_type, &self.balance));
+        self.nav_revision += 1;
+        true
+    }
+
+    /// Pay for and enqueue an item at the given building.
+    fn queue_production(&mut self, building_idx: usize, production_type: ProductionType) -> bool {
+        let can_produce = self.buildings.get(building_idx)
+            .is_some_and(|b| b.can_produce(production_type));
+        let cost = self.balance.production(production_type).cost.clone();
+        if !can_produce || !self.spend(&cost) {
+            return false;
+        }
+        self.buildings[building_idx].production_queue.push(production_type);
+        true
+    }
+
+    fn spawn_enemies(&mut self, delta_time: f32) {
+        for spawn in self.waves.update(delta_time, &self.balance, &mut self.rng) {
+            let mut enemy = match spawn.archetype {
+                _ if spawn.boss => Enemy::boss(self.next_enemy_id, spawn.position, &self.balance),
+                Some(archetype) => Enemy::of_archetype(self.next_enemy_id, spawn.position, archetype, &self.balance),
+                None => Enemy::new(self.next_enemy_id, spawn.position, spawn.size, &self.balance),
+            };
+            enemy.max_health = ((enemy.max_health as f32 * spawn.health_multiplier).round() as u32).max(1);
+            enemy.health = enemy.max_health;
+            self.enemies.push(enemy);
+            self.next_enemy_id += 1;
+        }
+    }
+
+    fn discover_ore(&mut self, delta_time: f32) {
+        let ore = &self.balance.ore;
+        if ore.discovery_interval <= 0.0 {
+            return;
+        }
+        self.ore_discovery_timer -= delta_time;
+        if self.ore_discovery_timer > 0.0 {
+            return;
+        }
+        self.ore_discovery_timer += ore.discovery_interval;
+        self.ore_discoveries += 1;
+        let push_out = self.ore_discoveries as f32 * ore.discovery_distance_step;
+        let (min, max) = (ore.field_distance_min + push_out, ore.field_distance_max + push_out);
+        let resource_type = self.random_ore();
+        self.generate_ore_patch(resource_type, min, max);
+    }
+
+    /// Pick an ore for a new patch according to `ore.weights`.
+    fn random_ore(&mut self) -> ResourceType {
+        let weights = &self.balance.ore.weights;
+        let total: u32 = weights.values().sum();
+        let mut roll = self.rng.gen_range(0..total.max(1));
+        for (resource_type, weight) in weights {
+            if roll < *weight {
+                return *resource_type;
+            }
+            roll -= weight;
+        }
+        ResourceType::Iron
+    }
+
+    /// Place a patch of `resource_type` at a random spot between `min_distance` and `max_distance` from
+    /// the beacon, clear of the existing patches. Further out means richer.
+    /// Gives up quietly if no free spot turns up after a few tries.
+    fn generate_ore_patch(&mut self, resource_type: ResourceType, min_distance: f32, max_distance: f32) {
+        const ATTEMPTS: u32 = 10;
+        const MIN_SPACING: f32 = 120.0;
+
+        let ore = &self.balance.ore;
+        for _ in 0..ATTEMPTS {
+            let angle = self.rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = self.rng.gen_range(min_distance..=max_distance);
+            let position = Pos2::new(angle.cos() * distance, angle.sin() * distance);
+            if self.ore_patches.iter().any(|p| (p.position - position).length() < MIN_SPACING) {
+                continue;
+            }
+
+            let richness = self.rng.gen_range(ore.richness_min..=ore.richness_max);
+            let amount = (richness as f32 * distance / ore.field_distance_min) as u32;
+            self.ore_patches.push(OrePatch::new(position, resource_type, amount));
+            return;
+        }
+    }
+
+    fn refresh_nav(&mut self) {
+        if self.nav.built_for != Some(self.nav_revision) {
+            self.nav.rebuild(&self.buildings, self.nav_revision);
+        }
+        if self.flow.built_for != Some(self.nav_revision)
+            && let Some(beacon) = self.buildings.iter().find(|b| b.building_type == BuildingType::Beacon) {
+            let radius = self.balance.spawn_distance_max + Self::FLOW_FIELD_MARGIN;
+            self.flow.rebuild(&self.nav, beacon.position, beacon.size, radius, self.nav_revision);
+        }
+    }
+
+    /// Push overlapping enemies apart so they don't stack on one spot.
+    fn separate_enemies(&mut self) {
+        let strength = self.balance.enemies.separation;
+        if strength <= 0.0 {
+            return;
+        }
+        self.index.reindex_enemies(&self.enemies);
+
+        let mut pushes = vec![Vec2::ZERO; self.enemies.len()];
+        for (i, enemy) in self.enemies.iter().enumerate().filter(|(_, e)| !e.burrowed) {
+            for j in self.index.enemies.query(enemy.position, enemy.radius()).filter(|&j| j > i) {
+                let other = &self.enemies[j];
+                let offset = enemy.position - other.position;
+                let overlap = enemy.radius() + other.radius() - offset.length();
+                if overlap <= 0.0 {
+                    continue;
+                }
+                // Exactly stacked enemies split along an angle picked from their ids
+                let away = if offset.length_sq() > 0.0 {
+                    offset.normalized()
+                } else {
+                    Vec2::angled(enemy.id as f32 * 2.4)
+                };
+                let push = away * overlap * strength * 0.5;
+                pushes[i] += push;
+                pushes[j] -= push;
+            }
+        }
+
+        let mut nearby = Vec::new();
+        for (enemy, push) in self.enemies.iter_mut().zip(pushes) {
+            if push == Vec2::ZERO {
+                continue;
+            }
+            nearby.clear();
+            nearby.extend(self.index.buildings.query(enemy.position, enemy.radius() + push.length()).map(|i| &self.buildings[i]));
+            if !enemy.hits_wall(enemy.position + push, &nearby) {
+                enemy.position += push;
+            }
+        }
+    }
+
+    fn update_enemies(&mut self, delta_time: f32) {
+        self.refresh_nav();
+        self.index.reindex_trucks(&self.trucks);
+        self.index.refresh_buildings(&self.buildings, self.nav_revision);
+
+        self.index.reindex_enemies(&self.enemies);
+
+        // Look around first, so every enemy decides on the same picture
+        let senses: Vec<Senses> = self.enemies.iter().map(|enemy| self.enemy_senses(enemy)).collect();
+
+        let mut nearby = Vec::new();
+        for (enemy, senses) in self.enemies.iter_mut().zip(&senses) {
+            let stats = enemy.stats(&self.balance);
+            if enemy.burrowed {
+                // Tunnel straight for the beacon, under walls and all
+                enemy.update(delta_time, Self::BEACON_POS, Self::BEACON_POS, &[], &self.balance);
+                if let Some(EnemyAbility::Burrow { surface_distance }) = stats.ability
+                    && (enemy.position - Self::BEACON_POS).length() <= surface_distance {
+                    enemy.burrowed = false;
+                    enemy.home = enemy.position;
+                }
+                continue;
+            }
+
+            // Heading for the beacon shares the flow field; anything else gets its own route
+            let (target, mut heading) = match enemy.think(delta_time, senses, &self.balance, &mut self.rng) {
+                Goal::Point(point) => {
+                    enemy.route.plan(&self.nav, enemy.position, point, self.nav_revision, Self::ENEMY_REPLAN_DISTANCE);
+                    (point, enemy.route.next_point(point))
+                }
+                Goal::Beacon => {
+                    let step = self.flow.next_point(&self.nav, enemy.position);
+                    (Self::BEACON_POS, step.unwrap_or(Self::BEACON_POS))
+                }
+            };
+
+            // Spitters stand off once their target is in reach
+            if let Some(EnemyAbility::Spit { range, .. }) = stats.ability
+                && enemy.behavior == EnemyBehavior::Attacking
+                && (target - enemy.position).length() <= range {
+                heading = enemy.position;
+            }
+
+            // Only walls within one step (plus the widest sidestep) can block
+            let speed = enemy.speed(&self.balance);
+            nearby.clear();
+            nearby.extend(self.index.buildings.query(enemy.position, enemy.radius() + speed * delta_time).map(|i| &self.buildings[i]));
+            enemy.update(delta_time, target, heading, &nearby, &self.balance);
+            enemy.route.advance(enemy.position);
+            enemy.being_shot_at = false; // Reset each tick
+        }
+        self.separate_enemies();
+        self.index.reindex_enemies(&self.enemies);
+
+        // Enemies attack trucks they are touching
+        for enemy in self.enemies.iter().filter(|e| !e.burrowed) {
+            let damage = enemy.stats(&self.balance).damage;
+            for i in self.index.trucks.query(enemy.position, enemy.radius()) {
+                let truck = &mut self.trucks[i];
+                let distance = (enemy.position - truck.position).length();
+                if distance < enemy.radius() + truck.size / 2.0 {
+                    truck.take_damage(damage, &self.balance.truck);
+                }
+            }
+        }
+
+        self.remove_destroyed_trucks();
+
+        // Enemies attack buildings when in range
+        for enemy in self.enemies.iter().filter(|e| !e.burrowed) {
+            for i in self.index.buildings.query(enemy.position, enemy.radius() + 1.0) {
+                let building = &mut self.buildings[i];
+                let distance = (enemy.position - building.position).length();
+                let attack_range = enemy.radius() + building.size;
+                // Walls stop enemies at their edge, so anything touching one is in range
+                let in_range = match building.building_type {
+                    BuildingType::Wall => building.overlaps_circle(enemy.position, enemy.radius() + 1.0),
+                    _ => distance < attack_range,
+                };
+
+                if in_range {
+                    // Deal damage based on enemy size
+                    let damage = enemy.stats(&self.balance).damage;
+                    building.health = building.health.saturating_sub(damage);
+                }
+            }
+        }
+
+        self.remove_destroyed_buildings();
+    }
+
+    /// What `enemy` can see: the closest truck and building in aggro range,
+    /// the armed trucks and loaded turrets among them, and the rest of its pack.
+    fn enemy_senses(&self, enemy: &Enemy) -> Senses {
+        let sight = self.balance.enemies.aggro_range;
+        let closest = |a: &Pos2, b: &Pos2| (*a - enemy.position).length_sq().total_cmp(&(*b - enemy.position).length_sq());
+        let armed = |t: &Truck| t.weapon.is_some() && t.ammo > 0;
+
+        let mut senses = Senses::default();
+        if enemy.burrowed {
+            return senses;
+        }
+        if enemy.boss.is_some() {
+            senses.factory = self.buildings.iter()
+                .filter(|b| b.building_type == BuildingType::Factory)
+                .map(|b| b.position)
+                .min_by(closest);
+        }
+        let in_sight: Vec<&Truck> = self.index.trucks.query(enemy.position, sight)
+            .map(|i| &self.trucks[i])
+            .filter(|t| (t.position - enemy.position).length() < sight)
+            .collect();
+        senses.truck = in_sight.iter().map(|t| t.position).min_by(closest);
+        senses.threats = in_sight.iter().filter(|t| armed(t)).count();
+
+        // Miner hunters look further, but only for unarmed trucks, and ignore buildings
+        if let Some(EnemyAbility::HuntMiners { sight }) = enemy.stats(&self.balance).ability {
+            senses.truck = self.index.trucks.query(enemy.position, sight)
+                .map(|i| &self.trucks[i])
+                .filter(|t| !armed(t) && (t.position - enemy.position).length() < sight)
+                .map(|t| t.position)
+                .min_by(closest);
+            return self.sense_pack(enemy, senses);
+        }
+
+        let buildings: Vec<&Building> = self.index.buildings.query(enemy.position, sight)
+            .map(|i| &self.buildings[i])
+            .filter(|b| b.distance_to(enemy.position) < sight)
+            .collect();
+        senses.threats += buildings.iter().filter(|b| b.building_type == BuildingType::Turret && b.bullets > 0).count();
+        // Walls are in the way rather than worth a detour, and the beacon is the default goal anyway
+        senses.building = buildings.iter()
+            .filter(|b| !matches!(b.building_type, BuildingType::Wall | BuildingType::Beacon))
+            .map(|b| b.position)
+            .min_by(closest);
+        self.sense_pack(enemy, senses)
+    }
+
+    /// Fill in the rest of `enemy`'s pack.
+    fn sense_pack(&self, enemy: &Enemy, mut senses: Senses) -> Senses {
+        let pack_radius = self.balance.enemies.pack_radius;
+        let mut sum = Vec2::ZERO;
+        for i in self.index.enemies.query(enemy.position, pack_radius) {
+            let other = &self.enemies[i];
+            if other.id != enemy.id && (other.position - enemy.position).length() < pack_radius {
+                sum += other.position.to_vec2();
+                senses.pack_size += 1;
+            }
+        }
+        if senses.pack_size > 0 {
+            senses.pack_center = Some((sum / senses.pack_size as f32).to_pos2());
+        }
+        senses
+    }
+
+    /// Move bosses through their phases as they lose health, and have
+    /// summoning bosses call up smalls around themselves.
+    fn update_bosses(&mut self, delta_time: f32) {
+        let boss = &self.balance.boss;
+        let mut summons = Vec::new();
+        for enemy in self.enemies.iter_mut().filter(|e| e.boss.is_some()) {
+            let share = enemy.health as f32 / enemy.max_health as f32;
+            let phase = BossPhase::for_health(share, boss);
+            if enemy.boss != Some(phase) {
+                enemy.boss = Some(phase);
+                enemy.fire_cooldown = 0.0;
+            }
+
+            if phase == BossPhase::Summoning {
+                enemy.fire_cooldown -= delta_time;
+                if enemy.fire_cooldown <= 0.0 {
+                    enemy.fire_cooldown += boss.summon_interval;
+                    summons.push((enemy.position, enemy.radius()));
+                }
+            }
+        }
+
+        let small_radius = self.balance.enemies.small.radius;
+        for (center, radius) in summons {
+            let count = self.balance.boss.summon_count;
+            for i in 0..count {
+                let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                let position = center + Vec2::angled(angle) * (radius + small_radius);
+                let mut enemy = Enemy::new(self.next_enemy_id, position, EnemySize::Small, &self.balance);
+                enemy.behavior = EnemyBehavior::Advancing;
+                self.enemies.push(enemy);
+                self.next_enemy_id += 1;
+            }
+        }
+    }
+
+    /// Destroyed trucks spill their cargo where they stood.
+    fn remove_destroyed_trucks(&mut self) {
+        for truck in self.trucks.iter().filter(|t| t.health == 0) {
+            if let Some(resource_type) = truck.cargo
+                && truck.cargo_amount > 0 {
+                drop_resource(&mut self.resource_piles, truck.position, resource_type, truck.cargo_amount);
+            }
+            self.stats.trucks_lost += 1;
+        }
+        let before = self.trucks.len();
+        self.trucks.retain(|t| t.health > 0);
+        if self.trucks.len() != before {
+            self.index.reindex_trucks(&self.trucks);
+        }
+    }
+
+    /// Remove destroyed buildings; losing the beacon ends the match.
+    fn remove_destroyed_buildings(&mut self) {
+        let before = self.buildings.len();
+        self.buildings.retain(|b| b.health > 0);
+        if self.buildings.len() != before {
+            self.nav_revision += 1;
+            // Later steps this tick may still look buildings up by index
+            self.index.refresh_buildings(&self.buildings, self.nav_revision);
+        }
+        self.stats.buildings_lost += (before - self.buildings.len()) as u32;
+    }
+
+    fn update_trucks(&mut self, delta_time: f32) {
+        self.refresh_nav();
+        self.index.refresh_buildings(&self.buildings, self.nav_revision);
+        self.index.refresh_ore_patches(&self.ore_patches);
+        self.index.reindex_resource_piles(&self.resource_piles);
+        let stats = &self.balance.truck;
+        for truck in &mut self.trucks {
+            let armed = truck.weapon.is_some() && truck.ammo > 0;
+            let weapon_range = truck.weapon.map_or(0.0, |w| self.balance.weapon(w).range);
+            let mut replan_tolerance = 0.0;
+            let engaged = match truck.order {
+                TruckOrder::None => false,
+                TruckOrder::Attack { enemy_id } => match self.enemies.iter().find(|e| e.id == enemy_id) {
+                    Some(enemy) => {
+                        let in_range = (enemy.position - truck.position).length() <= weapon_range;
+                        if !in_range && truck.stance == Stance::Free {
+                            // Chase, re-planning once the enemy has moved off
+                            truck.target = Some(enemy.position);
+                            truck.state = TruckState::Moving;
+                            truck.mining_patch = None;
+                            replan_tolerance = Self::ENEMY_REPLAN_DISTANCE;
+                        }
+                        in_range
+                    }
+                    None => {
+                        // Target is dead; stop where the chase ended
+                        truck.order = TruckOrder::None;
+                        if truck.state == TruckState::Moving {
+                            truck.state = TruckState::Idle;
+                            truck.target = None;
+                            truck.route.clear();
+                        }
+                        false
+                    }
+                },
+                TruckOrder::AttackMove => {
+                    if truck.target.is_none() {
+                        truck.order = TruckOrder::None;
+                    }
+                    armed && self.index.closest_enemy(&self.enemies, truck.position, weapon_range).is_some()
+                }
+            };
+            let halted = engaged || truck.stance == Stance::HoldPosition;
+
+            if let Some(target) = truck.target && !halted {
+                truck.route.plan(&self.nav, truck.position, target, self.nav_revision, replan_tolerance);
+            }
+            let mining_patch = truck.mining_patch;
+            let cargo_before = truck.cargo_amount;
+            truck.update(delta_time, stats, halted);
+
+            // Mined ore comes out of the patch; a patch can't give more than it has left
+            if let Some(patch_idx) = mining_patch {
+                let patch = &mut self.ore_patches[patch_idx];
+                let mined = truck.cargo_amount.saturating_sub(cargo_before);
+                let taken = mined.min(patch.amount);
+                patch.amount -= taken;
+                truck.cargo_amount -= mined - taken;
+                if patch.is_depleted() && truck.state == TruckState::Mining {
+                    leave_depleted_patch(truck, &self.ore_patches, patch_idx);
+                }
+            }
+
+            // Check if truck is at beacon to unload (any state, any amount)
+            let at_beacon = self.index.buildings.query(truck.position, Self::UNLOAD_RANGE)
+                .map(|i| &self.buildings[i])
+                .any(|b| b.building_type == BuildingType::Beacon && (truck.position - b.position).length() < Self::UNLOAD_RANGE);
+            if at_beacon && truck.cargo_amount > 0 {
+                // Unload cargo
+                if let Some(cargo) = truck.cargo {
+                    self.stockpile.add(cargo, truck.cargo_amount);
+                    self.stats.collected.add(cargo, truck.cargo_amount);
+                }
+                truck.cargo = None;
+                truck.cargo_amount = 0;
+                // If they were returning to base, they can now be idle
+                if truck.state == TruckState::ReturningToBase {
+                    truck.state = TruckState::Idle;
+                    truck.target = None;
+                }
+            }
+
+            // Auto-return to last mining position if empty and idle at beacon
+            if at_beacon && truck.state == TruckState::Idle && truck.cargo_amount == 0 && !truck.salvaging
+                && let Some(mining_pos) = truck.last_mining_position {
+                // Swap a worked-out patch for the nearest live one of the same ore
+                let depleted = self.ore_patches.iter().find(|p| p.contains_point(mining_pos) && p.is_depleted());
+                let destination = match depleted {
+                    Some(patch) => nearest_patch(&self.ore_patches, mining_pos, patch.resource_type)
+                        .map(|idx| self.ore_patches[idx].position),
+                    None => Some(mining_pos),
+                };
+                truck.last_mining_position = destination;
+                if let Some(destination) = destination {
+                    truck.start_moving(destination);
+                }
+            }
+
+            // Check if truck is at a factory to equip weapons
+            for i in self.index.buildings.query(truck.position, Self::EQUIP_RANGE) {
+                let building = &mut self.buildings[i];
+                if building.building_type != BuildingType::Factory {
+                    continue;
+                }
+                let factory_dist = (truck.position - building.position).length();
+                // Allow equipping even while moving, just need to be close
+                if factory_dist < Self::EQUIP_RANGE {
+                    // An unarmed truck takes the first weapon in stock
+                    if truck.weapon.is_none()
+                        && let Some((&weapon, count)) = building.stored_weapons.iter_mut().find(|(_, count)| **count > 0) {
+                        *count -= 1;
+                        truck.weapon = Some(weapon);
+                        truck.ammo = 0;
+                    }
+
+                    // Load whole boxes of the ammo the weapon takes
+                    if let Some(weapon) = truck.weapon {
+                        let weapon_stats = self.balance.weapon(weapon);
+                        let per_box = self.balance.ammo(weapon_stats.ammo).per_box;
+                        let stored = building.stored_ammo.entry(weapon_stats.ammo).or_default();
+                        let room = weapon_stats.max_ammo.saturating_sub(truck.ammo);
+                        let boxes_to_load = (room / per_box).min(*stored);
+                        truck.ammo += boxes_to_load * per_box;
+                        *stored -= boxes_to_load;
+                    }
+                }
+            }
+
+            // Pick up spilled cargo the truck has room for, then take it home
+            for i in self.index.resource_piles.query(truck.position, ResourcePile::PICKUP_RADIUS) {
+                let pile = &mut self.resource_piles[i];
+                let room = stats.max_cargo.saturating_sub(truck.cargo_amount);
+                // A mining truck's cargo type is already set before its first load comes in
+                let empty = truck.cargo_amount == 0 && truck.state != TruckState::Mining;
+                let compatible = truck.cargo.is_none_or(|c| c == pile.resource_type) || empty;
+                if room == 0 || pile.amount == 0 || !compatible || (pile.position - truck.position).length() > ResourcePile::PICKUP_RADIUS {
+                    continue;
+                }
+                let taken = room.min(pile.amount);
+                pile.amount -= taken;
+                truck.cargo = Some(pile.resource_type);
+                truck.cargo_amount += taken;
+                if truck.state == TruckState::Idle && !truck.salvaging {
+                    truck.return_to_base();
+                }
+            }
+
+            // Salvagers go from pile to pile until full or out of scrap, then head home
+            if truck.salvaging && truck.state == TruckState::Idle {
+                let next = nearest_scrap(&self.resource_piles, truck.position)
+                    .filter(|_| truck.cargo_amount < stats.max_cargo && truck.cargo.is_none_or(|c| c == ResourceType::Scrap))
+                    .map(|i| self.resource_piles[i].position);
+                match next {
+                    Some(pile) => {
+                        truck.start_moving(pile);
+                        truck.salvaging = true;
+                    }
+                    None if truck.cargo_amount > 0 => truck.return_to_base(),
+                    None => truck.salvaging = false,
+                }
+            }
+
+            // Check if truck is on an ore patch and should start mining
+            if truck.state == TruckState::Idle && truck.cargo_amount < stats.max_cargo
+                && let Some(patch_idx) = self.index.ore_patches.query(truck.position, 0.0)
+                    .filter(|&i| self.ore_patches[i].contains_point(truck.position) && !self.ore_patches[i].is_depleted())
+                    .min() {
+                // Cargo of another kind picked up on the way goes home before mining starts
+                let resource_type = self.ore_patches[patch_idx].resource_type;
+                if truck.cargo_amount > 0 && truck.cargo != Some(resource_type) {
+                    truck.return_to_base();
+                } else {
+                    truck.start_mining(resource_type, patch_idx);
+                }
+            }
+        }
+        self.resource_piles.retain(|p| p.amount > 0);
+        self.separate_trucks();
+        self.index.reindex_trucks(&self.trucks);
+    }
+
+    /// Where each selected truck would go for a group move to `target`, as
+    /// `(truck index, slot)` pairs. Armed trucks take the front slots and
+    /// miners fill in behind, nearest trucks first within each.
+    pub fn formation_slots(&self, target: Pos2, formation: Formation, facing: Option<Vec2>) -> Vec<(usize, Pos2)> {
+        let mut selected: Vec<usize> = (0..self.trucks.len()).filter(|&i| self.trucks[i].selected).collect();
+        if selected.is_empty() {
+            return Vec::new();
+        }
+        selected.sort_by(|&a, &b| {
+            let (ta, tb) = (&self.trucks[a], &self.trucks[b]);
+            let da = (ta.position - target).length_sq();
+            let db = (tb.position - target).length_sq();
+            tb.weapon.is_some().cmp(&ta.weapon.is_some()).then(da.total_cmp(&db)).then(a.cmp(&b))
+        });
+
+        // Without a drag direction, face the way the group is travelling
+        let facing = facing.unwrap_or_else(|| {
+            let sum = selected.iter().fold(Vec2::ZERO, |sum, &i| sum + self.trucks[i].position.to_vec2());
+            target - (sum / selected.len() as f32).to_pos2()
+        });
+        let mut slots = formation.slots(target, facing, selected.len(), Self::FORMATION_SPACING);
+
+        // Keep a group sent to an ore patch on the patch so every truck mines
+        if let Some(patch) = self.ore_patches.iter().find(|p| p.contains_point(target)) {
+            let reach = patch.size * 0.9;
+            for slot in &mut slots {
+                let offset = *slot - patch.position;
+                if offset.length() > reach {
+                    *slot = patch.position + offset.normalized() * reach;
+                }
+            }
+        }
+        selected.into_iter().zip(slots).collect()
+    }
+
+    /// Push trucks apart from each other and out of buildings, so groups
+    /// spread out instead of sharing a spot. A truck headed into a building
+    /// (like the beacon to unload) is let in, and one holding position
+    /// stays where it is.
+    fn separate_trucks(&mut self) {
+        let strength = self.balance.truck.separation;
+        if strength <= 0.0 {
+            return;
+        }
+        self.index.reindex_trucks(&self.trucks);
+
+        let mut pushes = vec![Vec2::ZERO; self.trucks.len()];
+        for (i, truck) in self.trucks.iter().enumerate() {
+            let radius = truck.size / 2.0;
+            for j in self.index.trucks.query(truck.position, radius).filter(|&j| j > i) {
+                let other = &self.trucks[j];
+                let offset = truck.position - other.position;
+                let overlap = radius + other.size / 2.0 - offset.length();
+                if overlap <= 0.0 {
+                    continue;
+                }
+                // Exactly stacked trucks split along an angle picked from their ids
+                let away = if offset.length_sq() > 0.0 {
+                    offset.normalized()
+                } else {
+                    Vec2::angled(truck.id as f32 * 2.4)
+                };
+                // A truck holding position doesn't budge, so the other one moves the whole way
+                let push = away * overlap * strength;
+                let (share_i, share_j) = match (truck.stance, other.stance) {
+                    (Stance::Free, Stance::Free) => (0.5, 0.5),
+                    (Stance::Free, Stance::HoldPosition) => (1.0, 0.0),
+                    (Stance::HoldPosition, Stance::Free) => (0.0, 1.0),
+                    (Stance::HoldPosition, Stance::HoldPosition) => (0.0, 0.0),
+                };
+                pushes[i] += push * share_i;
+                pushes[j] -= push * share_j;
+            }
+
+            for b in self.index.buildings.query(truck.position, radius) {
+                let building = &self.buildings[b];
+                if truck.target.is_some_and(|t| building.contains_point(t))
+                    || !building.overlaps_circle(truck.position, radius) {
+                    continue;
+                }
+                // Out through the nearest side of the square footprint
+                let offset = truck.position - building.position;
+                let (depth_x, depth_y) = (building.size + radius - offset.x.abs(), building.size + radius - offset.y.abs());
+                pushes[i] += if depth_x < depth_y {
+                    Vec2::new(depth_x * offset.x.signum(), 0.0)
+                } else {
+                    Vec2::new(0.0, depth_y * offset.y.signum())
+                } * strength;
+            }
+        }
+
+        for (truck, push) in self.trucks.iter_mut().zip(pushes) {
+            if truck.stance == Stance::Free {
+                truck.position += push;
+            }
+        }
+    }
+
+    fn update_turrets(&mut self, delta_time: f32) {
+        self.index.refresh_buildings(&self.buildings, self.nav_revision);
+        let stats = &self.balance.turret;
+        let box_size = self.balance.ammo.bullets.per_box;
+        for idx in 0..self.buildings.len() {
+            let turret = &mut self.buildings[idx];
+            if turret.building_type != BuildingType::Turret {
+                continue;
+            }
+            if turret.fire_cooldown > 0.0 {
+                turret.fire_cooldown -= delta_time;
+            }
+            if turret.bullets + box_size > stats.max_bullets {
+                continue;
+            }
+
+            // Pull one box at a time from the first factory in supply range
+            let position = turret.position;
+            let supplier = self.index.buildings.query(position, stats.supply_range)
+                .filter(|&i| {
+                    let b = &self.buildings[i];
+                    b.building_type == BuildingType::Factory
+                        && b.stored_ammo.get(&AmmoType::Bullets).is_some_and(|&boxes| boxes > 0)
+                        && (b.position - position).length() < stats.supply_range
+                })
+                .min();
+            if let Some(factory_idx) = supplier {
+                if let Some(boxes) = self.buildings[factory_idx].stored_ammo.get_mut(&AmmoType::Bullets) {
+                    *boxes -= 1;
+                }
+                self.buildings[idx].bullets += box_size;
+            }
+        }
+    }
+
+    /// Trucks and turrets whose guns are ready fire a projectile at their target.
+    fn process_attacks(&mut self) {
+        // Armed trucks fire at their attack target, or else the closest enemy in range
+        let targets: Vec<Option<usize>> = self.trucks.iter().map(|t| self.truck_firing_target(t)).collect();
+        for (truck, target) in self.trucks.iter_mut().zip(targets) {
+            if let Some(weapon) = truck.weapon && truck.ammo > 0 && truck.fire_cooldown <= 0.0
+                && let Some(enemy_idx) = target {
+                let stats = self.balance.weapon(weapon);
+                truck.ammo -= 1;
+                truck.fire_cooldown = stats.fire_rate;
+                let aim = self.enemies[enemy_idx].position;
+                truck.aim = aim - truck.position;
+
+                if stats.cone_angle > 0.0 {
+                    // Cone weapons hit everything in front of them at once
+                    let min_cos = (stats.cone_angle.to_radians() / 2.0).cos();
+                    let facing = truck.aim.normalized();
+                    for idx in self.index.enemies.query(truck.position, stats.range) {
+                        let enemy = &mut self.enemies[idx];
+                        let offset = enemy.position - truck.position;
+                        let distance = offset.length();
+                        let inside = distance <= 0.0
+                            || (distance <= stats.range + enemy.radius()
+                                && facing.dot(offset / distance) >= min_cos);
+                        if inside {
+                            enemy.take_damage(stats.damage, &self.balance);
+                        }
+                    }
+                } else {
+                    self.projectiles.push(
+                        Projectile::new(truck.position, aim, stats.projectile_speed, stats.damage)
+                            .with_splash(stats.splash_radius, stats.splash_falloff),
+                    );
+                }
+            }
+        }
+
+        // Turrets use the same targeting as trucks
+        let stats = &self.balance.turret;
+        for turret in &mut self.buildings {
+            if turret.building_type == BuildingType::Turret && turret.bullets > 0 && turret.fire_cooldown <= 0.0
+                && let Some(enemy_idx) = self.index.closest_enemy(&self.enemies, turret.position, stats.weapon_range) {
+                turret.bullets -= 1;
+                turret.fire_cooldown = stats.fire_rate;
+                let aim = self.enemies[enemy_idx].position;
+                self.projectiles.push(Projectile::new(turret.position, aim, stats.projectile_speed, stats.bullet_damage));
+            }
+        }
+    }
+
+    /// Fly every shot one tick, applying hits and clearing out the dead.
+    fn update_projectiles(&mut self, delta_time: f32) {
+        let mut bursts = Vec::new();
+        for projectile in &mut self.projectiles {
+            let hit = projectile.advance(delta_time, &self.enemies, &self.index.enemies);
+            if projectile.splash_radius > 0.0 {
+                if projectile.is_spent() {
+                    let blast = BlastStats {
+                        radius: projectile.splash_radius,
+                        damage: projectile.damage,
+                        falloff: projectile.splash_falloff,
+                    };
+                    bursts.push((projectile.position, blast));
+                }
+            } else if let Some(idx) = hit {
+                self.enemies[idx].take_damage(projectile.damage, &self.balance);
+            }
+        }
+        self.projectiles.retain(|p| !p.is_spent());
+
+        for (center, blast) in bursts {
+            self.blast_enemies(center, blast);
+        }
+        self.remove_dead_enemies();
+    }
+
+    /// Splash damage to every enemy caught in a blast, measured to its edge.
+    fn blast_enemies(&mut self, center: Pos2, blast: BlastStats) {
+        self.explosions.push(Explosion::new(center, blast.radius));
+        for idx in self.index.enemies.query(center, blast.radius) {
+            let enemy = &mut self.enemies[idx];
+            let distance = ((enemy.position - center).length() - enemy.radius()).max(0.0);
+            if let Some(damage) = falloff_damage(blast.damage, blast.radius, blast.falloff, distance) {
+                enemy.take_damage(damage, &self.balance);
+            }
+        }
+    }
+
+    /// Clear out killed enemies. Those that explode on death damage the
+    /// buildings and trucks around them, and splitters break into smalls.
+    fn remove_dead_enemies(&mut self) {
+        let dead: Vec<(Pos2, &EnemyStats)> = self.enemies.iter()
+            .filter(|e| e.health == 0)
+            .map(|e| (e.position, e.stats(&self.balance)))
+            .collect();
+        let blasts: Vec<(Pos2, BlastStats)> = dead.iter()
+            .filter_map(|(position, stats)| stats.explosion.map(|blast| (*position, blast)))
+            .collect();
+        let splits: Vec<(Pos2, u32)> = dead.iter()
+            .filter_map(|(position, stats)| match stats.ability {
+                Some(EnemyAbility::Split { count }) => Some((*position, count)),
+                _ => None,
+            })
+            .collect();
+
+        // Kills leave scrap behind, and bosses always leave their loot
+        let loot = &self.balance.boss.loot;
+        for enemy in self.enemies.iter().filter(|e| e.health == 0) {
+            let drops: Vec<(f32, ResourceType, u32)> = if enemy.boss.is_some() {
+                // One pile per kind, laid out in a row across the wreck
+                let middle = (loot.iter().count() as f32 - 1.0) / 2.0;
+                loot.iter()
+                    .enumerate()
+                    .map(|(i, (resource_type, amount))| (i as f32 - middle, resource_type, amount))
+                    .collect()
+            } else {
+                vec![(0.0, ResourceType::Scrap, self.balance.enemies.scrap.for_size(enemy.size))]
+            };
+            for (offset, resource_type, amount) in drops {
+                let position = enemy.position + Vec2::new(offset * enemy.radius() / 2.0, 0.0);
+                drop_resource(&mut self.resource_piles, position, resource_type, amount);
+            }
+        }
+
+        let before = self.enemies.len();
+        self.enemies.retain(|e| e.health > 0);
+        self.stats.enemies_killed += (before - self.enemies.len()) as u32;
+
+        let spacing = self.balance.enemies.small.radius * 2.0;
+        for (center, count) in splits {
+            for position in cluster_slots(center, count as usize, spacing) {
+                let mut enemy = Enemy::new(self.next_enemy_id, position, EnemySize::Small, &self.balance);
+                enemy.behavior = EnemyBehavior::Advancing;
+                self.enemies.push(enemy);
+                self.next_enemy_id += 1;
+            }
+        }
+        self.index.reindex_enemies(&self.enemies);
+
+        if blasts.is_empty() {
+            return;
+        }
+        for (center, blast) in blasts {
+            self.blast_base(center, blast);
+        }
+        self.remove_destroyed_trucks();
+        self.remove_destroyed_buildings();
+    }
+
+    /// Blast damage to the buildings and trucks around `center`, measured
+    /// to their edges. The caller clears out whatever it destroys.
+    fn blast_base(&mut self, center: Pos2, blast: BlastStats) {
+        self.explosions.push(Explosion::new(center, blast.radius));
+        for i in self.index.buildings.query(center, blast.radius) {
+            let building = &mut self.buildings[i];
+            if let Some(damage) = falloff_damage(blast.damage, blast.radius, blast.falloff, building.distance_to(center)) {
+                building.health = building.health.saturating_sub(damage);
+            }
+        }
+        for i in self.index.trucks.query(center, blast.radius) {
+            let truck = &mut self.trucks[i];
+            let distance = ((truck.position - center).length() - truck.size / 2.0).max(0.0);
+            if let Some(damage) = falloff_damage(blast.damage, blast.radius, blast.falloff, distance) {
+                truck.take_damage(damage, &self.balance.truck);
+            }
+        }
+    }
+
+    /// Spitters lob acid at whatever they are attacking once it is in
+    /// reach. Globs fly over everything and burst where they land.
+    fn update_spit(&mut self, delta_time: f32) {
+        for enemy in &mut self.enemies {
+            // Bosses keep their summon timer in `fire_cooldown`, so only spitters count it down here
+            let Some(EnemyAbility::Spit { range, damage, fire_rate, projectile_speed, splash_radius }) = enemy.stats(&self.balance).ability else {
+                continue;
+            };
+            enemy.fire_cooldown -= delta_time;
+            if enemy.behavior == EnemyBehavior::Attacking
+                && !enemy.burrowed
+                && enemy.fire_cooldown <= 0.0
+                && let Some(target) = enemy.target
+                && (target - enemy.position).length() <= range {
+                enemy.fire_cooldown = fire_rate;
+                self.spit.push(Projectile::new(enemy.position, target, projectile_speed, damage).with_splash(splash_radius, 0.0));
+            }
+        }
+
+        let mut bursts = Vec::new();
+        for glob in &mut self.spit {
+            glob.fly(delta_time);
+            if glob.is_spent() {
+                bursts.push((glob.position, BlastStats { radius: glob.splash_radius, damage: glob.damage, falloff: glob.splash_falloff }));
+            }
+        }
+        self.spit.retain(|g| !g.is_spent());
+
+        if bursts.is_empty() {
+            return;
+        }
+        for (center, blast) in bursts {
+            self.blast_base(center, blast);
+        }
+        self.remove_destroyed_trucks();
+        self.remove_destroyed_buildings();
+    }
+
+    fn fade_explosions(&mut self, delta_time: f32) {
+        for explosion in &mut self.explosions {
+            explosion.age += delta_time;
+        }
+        self.explosions.retain(|e| e.age < Explosion::DURATION);
+    }
+
+    fn update_production(&mut self, delta_time: f32) {
+        for building in &mut self.buildings {
+            if building.production_queue.is_empty() {
+                continue;
+            }
+            let current = building.production_queue[0];
+            building.production_progress += delta_time;
+
+            if building.production_progress >= self.balance.production(current).time {
+                building.production_progress = 0.0;
+                building.production_queue.remove(0);
+
+                // Produce the item
+                match current {
+                    ProductionType::Truck => {
+                        let offset_x = (self.next_truck_id as f32 % 3.0) * 30.0 - 30.0;
+                        let new_truck = Truck::new(
+                            self.next_truck_id,
+                            Pos2::new(building.position.x + offset_x, building.position.y + 60.0),
+                            &self.balance.truck,
+                        );
+                        self.trucks.push(new_truck);
+                        self.next_truck_id += 1;
+                        self.stats.trucks_built += 1;
+                    }
+                    ProductionType::Weapon(weapon) => {
+                        *building.stored_weapons.entry(weapon).or_default() += 1;
+                    }
+                    ProductionType::Ammo(ammo) => {
+                        *building.stored_ammo.entry(ammo).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Move a mining truck off a patch that just ran dry: unload if it has
+/// anything on board, otherwise head straight for the nearest patch of
+/// the same ore. Either way the auto-return goes to that patch next.
+fn leave_depleted_patch(truck: &mut Truck, patches: &[OrePatch], patch_idx: usize) {
+    let depleted = &patches[patch_idx];
+    let replacement = nearest_patch(patches, depleted.position, depleted.resource_type)
+        .map(|idx| patches[idx].position);
+    truck.last_mining_position = replacement;
+    if truck.cargo_amount > 0 {
+        truck.return_to_base();
+    } else {
+        truck.state = TruckState::Idle;
+        truck.mining_patch = None;
+        match replacement {
+            Some(position) => truck.start_moving(position),
+            None => truck.cargo = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(seed: u64) -> String {
+        let balance = Balance { grace_period: 5.0, ..Balance::default() };
+        let mut sim = Simulation::new(seed, balance);
+        let iron = sim.ore_patches[0].position;
+        for tick in 0..60 * 30 {
+            if tick == 30 {
+                sim.apply(&Command::SelectTrucks { truck_ids: vec![1, 2], toggle: false });
+                sim.apply(&Command::MoveSelected { target: iron, formation: Formation::default(), facing: None, attack: false });
+            }
+            sim.step();
+        }
+        ron::to_string(&sim).unwrap()
+    }
+
+    #[test]
+    fn spit_after_a_death_blast_sees_the_buildings_that_are_left() {
+        let balance = Balance { grace_period: 1000.0, ..Balance::default() };
+        let mut sim = Simulation::new(1, balance);
+        let spot = Pos2::new(400.0, 0.0);
+        let mut wall = Building::new(spot, BuildingType::Wall, &sim.balance);
+        wall.health = 1;
+        sim.buildings.push(wall);
+        sim.nav_revision += 1;
+
+        // The Large's death blast takes out the wall, then a glob lands on the same spot
+        let mut large = Enemy::new(0, spot + Vec2::new(0.0, 70.0), EnemySize::Large, &sim.balance);
+        large.health = 0;
+        sim.enemies.push(large);
+        sim.spit.push(Projectile::new(spot, spot, 100.0, 5).with_splash(20.0, 0.0));
+        sim.step();
+
+        assert_eq!(sim.buildings.len(), 1);
+        assert_eq!(sim.buildings[0].building_type, BuildingType::Beacon);
+        assert_eq!(sim.buildings[0].health, sim.balance.buildings.beacon.max_health);
+    }
+
+    #[test]
+    fn trucks_move_on_from_a_worked_out_patch_to_the_nearest_of_the_same_ore() {
+        let balance = Balance { grace_period: 1000.0, ..Balance::default() };
+        let mut sim = Simulation::new(1, balance);
+        sim.ore_patches[0].amount = 5;
+        let first = sim.ore_patches[0].position;
+        let next = first + Vec2::new(-100.0, 0.0);
+        sim.ore_patches.push(OrePatch::new(next, ResourceType::Iron, 1000));
+        let next_idx = sim.ore_patches.len() - 1;
+
+        sim.apply(&Command::SelectTrucks { truck_ids: vec![1], toggle: false });
+        sim.apply(&Command::MoveSelected { target: first, formation: Formation::default(), facing: None, attack: false });
+        for _ in 0..60 * 40 {
+            sim.step();
+        }
+
+        assert!(sim.ore_patches[0].is_depleted());
+        assert!(sim.ore_patches[next_idx].amount < 1000);
+        assert!(sim.stockpile.get(ResourceType::Iron) >= 5);
+        let truck = sim.trucks.iter().find(|t| t.id == 1).unwrap();
+        assert_eq!(truck.last_mining_position, Some(next));
+    }
+
+    #[test]
+    fn trucks_drive_around_a_wall_rather_than_through_it() {
+        let balance = Balance { grace_period: 1000.0, ..Balance::default() };
+        let mut sim = Simulation::new(1, balance);
+        sim.stockpile.add(ResourceType::Stone, 1000);
+        assert!(sim.apply(&Command::PlaceWall { start: Pos2::new(200.0, -100.0), end: Pos2::new(200.0, 200.0) }));
+        let size = sim.balance.buildings.wall.size;
+        let target = Pos2::new(300.0, 50.0);
+
+        sim.apply(&Command::SelectTrucks { truck_ids: vec![1], toggle: false });
+        sim.apply(&Command::MoveSelected { target, formation: Formation::default(), facing: None, attack: false });
+        for _ in 0..60 * 20 {
+            sim.step();
+            let truck = sim.trucks.iter().find(|t| t.id == 1).unwrap();
+            for wall in sim.buildings.iter().filter(|b| b.building_type == BuildingType::Wall) {
+                let offset = truck.position - wall.position;
+                assert!(offset.x.abs() >= size || offset.y.abs() >= size, "truck drove into the wall at {:?}", truck.position);
+            }
+        }
+        let truck = sim.trucks.iter().find(|t| t.id == 1).unwrap();
+        assert!((truck.position - target).length() < 5.0);
+    }
+
+    /// Armed truck 0 told to attack a far-off enemy while a closer one sits
+    /// in range. Returns the truck's start and end positions and the target
+    /// and decoy health after ten seconds.
+    fn attack_far_enemy(stance: Stance) -> (Pos2, Pos2, u32, u32) {
+        let mut balance = Balance { grace_period: 1000.0, ..Balance::default() };
+        balance.enemies.small.speed = 0.0;
+        let mut sim = Simulation::new(1, balance);
+        let start = sim.trucks[0].position;
+        for (id, offset) in [(0, Vec2::new(0.0, 350.0)), (1, Vec2::new(-100.0, 0.0))] {
+            let mut enemy = Enemy::new(id, start + offset, EnemySize::Small, &sim.balance);
+            enemy.health = 1000;
+            enemy.max_health = 1000;
+            sim.enemies.push(enemy);
+        }
+        sim.next_enemy_id = 2;
+
+        sim.apply(&Command::SelectTrucks { truck_ids: vec![0], toggle: false });
+        sim.apply(&Command::SetStance { stance });
+        assert!(sim.apply(&Command::AttackEnemy { enemy_id: 0 }));
+        for _ in 0..60 * 10 {
+            sim.step();
+        }
+        let health = |id| sim.enemies.iter().find(|e| e.id == id).unwrap().health;
+        (start, sim.trucks[0].position, health(0), health(1))
+    }
+
+    #[test]
+    fn attack_orders_chase_one_enemy_unless_holding_position() {
+        let (start, end, target, decoy) = attack_far_enemy(Stance::Free);
+        assert!((end - start).length() > 150.0);
+        assert!(target < 1000);
+        assert_eq!(decoy, 1000);
+
+        let (start, end, target, decoy) = attack_far_enemy(Stance::HoldPosition);
+        assert!((end - start).length() < 1.0);
+        assert_eq!(target, 1000);
+        assert!(decoy < 1000);
+    }
+
+    #[test]
+    fn same_seed_and_commands_give_the_same_match() {
+        assert_eq!(play(42), play(42));
+        assert_ne!(play(42), play(43));
+    }
+}
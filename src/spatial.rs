@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use egui::Pos2;
+use crate::building::Building;
+use crate::enemy::Enemy;
+use crate::resource::{OrePatch, ResourcePile};
+use crate::truck::Truck;
+
+/// Uniform-grid bucket index over a list of items, so range queries only
+/// look at items in nearby cells. Stores indices into the caller's list;
+/// rebuild it whenever that list changes or its items move.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Largest item extent, so queries also catch items whose edge, not
+    /// just their center, is in range.
+    reach: f32,
+}
+
+impl SpatialGrid {
+    pub const CELL_SIZE: f32 = 100.0;
+
+    /// Replace the contents with `(position, extent)` pairs, indexed in order.
+    pub fn rebuild(&mut self, items: impl IntoIterator<Item = (Pos2, f32)>) {
+        self.rebuild_indexed(items.into_iter().enumerate().map(|(idx, (position, extent))| (idx, position, extent)));
+    }
+
+    /// Like `rebuild`, for `(index, position, extent)` triples, so items
+    /// can be left out without shifting the indices of the rest.
+    pub fn rebuild_indexed(&mut self, items: impl IntoIterator<Item = (usize, Pos2, f32)>) {
+        self.cells.clear();
+        self.reach = 0.0;
+        for (idx, position, extent) in items {
+            self.cells.entry(Self::cell_at(position)).or_default().push(idx);
+            self.reach = self.reach.max(extent);
+        }
+    }
+
+    /// Indices of items that may lie within `radius` of `center`. This is a
+    /// superset; callers still check the exact distance. Order is fixed for
+    /// a given grid, so results stay deterministic.
+    pub fn query(&self, center: Pos2, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let reach = radius + self.reach;
+        let min = Self::cell_at(Pos2::new(center.x - reach, center.y - reach));
+        let max = Self::cell_at(Pos2::new(center.x + reach, center.y + reach));
+        (min.0..=max.0)
+            .flat_map(move |x| (min.1..=max.1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    fn cell_at(position: Pos2) -> (i32, i32) {
+        (
+            (position.x / Self::CELL_SIZE).floor() as i32,
+            (position.y / Self::CELL_SIZE).floor() as i32,
+        )
+    }
+}
+
+/// One grid per kind of entity the simulation runs range queries on.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+    pub enemies: SpatialGrid,
+    pub trucks: SpatialGrid,
+    pub buildings: SpatialGrid,
+    pub ore_patches: SpatialGrid,
+    /// Piles with something left in them.
+    pub resource_piles: SpatialGrid,
+    /// `Simulation::nav_revision` the building grid was built from.
+    buildings_for: Option<u64>,
+    patches_indexed: usize,
+}
+
+impl SpatialIndex {
+    /// Burrowed enemies are left out, so nothing can find or hit them.
+    pub fn reindex_enemies(&mut self, enemies: &[Enemy]) {
+        self.enemies.rebuild_indexed(enemies.iter().enumerate()
+            .filter(|(_, e)| !e.burrowed)
+            .map(|(idx, e)| (idx, e.position, e.radius())));
+    }
+
+    pub fn reindex_trucks(&mut self, trucks: &[Truck]) {
+        self.trucks.rebuild(trucks.iter().map(|t| (t.position, t.size / 2.0)));
+    }
+
+    /// Buildings only change on placement or destruction, which bumps `revision`.
+    pub fn refresh_buildings(&mut self, buildings: &[Building], revision: u64) {
+        if self.buildings_for != Some(revision) {
+            // Footprints are square, so the corner is the furthest point
+            self.buildings.rebuild(buildings.iter().map(|b| (b.position, b.size * std::f32::consts::SQRT_2)));
+            self.buildings_for = Some(revision);
+        }
+    }
+
+    /// Patches are never removed, so a change in count means new ones.
+    pub fn refresh_ore_patches(&mut self, patches: &[OrePatch]) {
+        if self.patches_indexed != patches.len() {
+            self.ore_patches.rebuild(patches.iter().map(|p| (p.position, p.size)));
+            self.patches_indexed = patches.len();
+        }
+    }
+
+    pub fn reindex_resource_piles(&mut self, piles: &[ResourcePile]) {
+        self.resource_piles.rebuild_indexed(piles.iter().enumerate()
+            .filter(|(_, p)| p.amount > 0)
+            .map(|(idx, p)| (idx, p.position, 0.0)));
+    }
+
+    /// Index of the enemy closest to `position` within `range`.
+    pub fn closest_enemy(&self, enemies: &[Enemy], position: Pos2, range: f32) -> Option<usize> {
+        self.enemies.query(position, range)
+            .map(|idx| (idx, (enemies[idx].position - position).length()))
+            .filter(|(_, distance)| *distance <= range)
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+            .map(|(idx, _)| idx)
+    }
+}
@@ -1,7 +1,11 @@
 use egui::{Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
 use crate::resource::ResourceType;
+use crate::balance::TruckStats;
+use crate::navigation::Route;
+use crate::weapon::WeaponType;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum TruckState {
     Idle,
     Moving,
@@ -9,11 +13,35 @@ pub enum TruckState {
     ReturningToBase,
 }
 
-#[derive(Debug, Clone)]
+/// How a truck holds its ground.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Stance {
+    /// Goes where it is sent and chases what it is told to attack.
+    #[default]
+    Free,
+    /// Never moves: fires at what comes in range, is not pushed aside, and
+    /// stays put until given a new move order.
+    HoldPosition,
+}
+
+/// Combat order a truck is carrying out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TruckOrder {
+    #[default]
+    None,
+    /// Chase the enemy with this id and fire only at it while it is in range.
+    Attack { enemy_id: usize },
+    /// Head for `target`, stopping to fight anything that comes in range.
+    AttackMove,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Truck {
     pub id: usize,
     pub position: Pos2,
     pub target: Option<Pos2>,
+    /// Way around buildings to `target`; planned by the simulation.
+    pub route: Route,
     pub selected: bool,
     pub size: f32,
     pub state: TruckState,
@@ -21,20 +49,30 @@ pub struct Truck {
     pub cargo_amount: u32,
     pub mining_progress: f32,
     pub last_mining_position: Option<Pos2>,
-    pub has_gun: bool,
-    pub bullets: u32,
+    /// Index into `Simulation::ore_patches` of the patch being mined.
+    pub mining_patch: Option<usize>,
+    /// Working through the scrap piles lying around, taking loads home
+    /// until none are left.
+    pub salvaging: bool,
+    pub weapon: Option<WeaponType>,
+    /// Rounds loaded for `weapon`.
+    pub ammo: u32,
     pub fire_cooldown: f32,
+    /// Direction of the last shot.
+    pub aim: Vec2,
+    pub health: u32,
+    pub max_health: u32,
+    pub order: TruckOrder,
+    pub stance: Stance,
 }
 
 impl Truck {
-    pub const WEAPON_RANGE: f32 = 150.0;
-    pub const FIRE_RATE: f32 = 0.5; // seconds between shots
-    
-    pub fn new(id: usize, position: Pos2) -> Self {
+    pub fn new(id: usize, position: Pos2, stats: &TruckStats) -> Self {
         Self {
             id,
             position,
             target: None,
+            route: Route::default(),
             selected: false,
             size: 20.0,
             state: TruckState::Idle,
@@ -42,13 +80,22 @@ impl Truck {
             cargo_amount: 0,
             mining_progress: 0.0,
             last_mining_position: None,
-            has_gun: false,
-            bullets: 0,
+            mining_patch: None,
+            salvaging: false,
+            weapon: None,
+            ammo: 0,
             fire_cooldown: 0.0,
+            aim: Vec2::ZERO,
+            health: stats.max_health,
+            max_health: stats.max_health,
+            order: TruckOrder::None,
+            stance: Stance::Free,
         }
     }
     
-    pub fn update(&mut self, delta_time: f32) {
+    /// Advance one tick. A `halted` truck keeps its destination but doesn't
+    /// drive toward it, e.g. while it stops to fight.
+    pub fn update(&mut self, delta_time: f32, stats: &TruckStats, halted: bool) {
         // Update fire cooldown
         if self.fire_cooldown > 0.0 {
             self.fire_cooldown -= delta_time;
@@ -64,20 +111,35 @@ impl Truck {
                     self.cargo_amount += 1;
                     
                     // Check if full
-                    if self.cargo_amount >= 64 {
-                        self.state = TruckState::ReturningToBase;
-                        self.target = Some(Pos2::new(0.0, 0.0)); // Head to beacon
+                    if self.cargo_amount >= stats.max_cargo {
+                        self.return_to_base();
                     }
                 }
             }
             TruckState::Moving | TruckState::ReturningToBase => {
+                if halted {
+                    return;
+                }
                 if let Some(target) = self.target {
+                    // Follow the route's waypoints before heading for the target itself
+                    if !self.route.waypoints.is_empty() {
+                        let waypoint = self.route.next_point(target);
+                        let to_waypoint = waypoint - self.position;
+                        let step = stats.speed * delta_time;
+                        if to_waypoint.length() <= step {
+                            self.position = waypoint;
+                        } else {
+                            self.position += to_waypoint.normalized() * step;
+                        }
+                        self.route.advance(self.position);
+                        return;
+                    }
+                    
                     let direction = target - self.position;
                     let distance = direction.length();
                     
                     if distance > 2.0 {
-                        let speed = 100.0; // pixels per second
-                        let movement = direction.normalized() * speed * delta_time;
+                        let movement = direction.normalized() * stats.speed * delta_time;
                         
                         if movement.length() < distance {
                             self.position += movement;
@@ -120,19 +182,40 @@ impl Truck {
             self.target = Some(target);
             self.state = TruckState::Moving;
             self.mining_progress = 0.0;
+            self.mining_patch = None;
+            self.salvaging = false;
+            self.route.clear();
+            self.order = TruckOrder::None;
         }
     }
     
-    pub fn start_mining(&mut self, resource_type: ResourceType) {
+    /// Head back to the beacon to unload whatever is on board.
+    pub fn return_to_base(&mut self) {
+        self.state = TruckState::ReturningToBase;
+        self.target = Some(Pos2::new(0.0, 0.0));
+        self.mining_patch = None;
+        self.route.clear();
+        self.order = TruckOrder::None;
+    }
+    
+    pub fn start_mining(&mut self, resource_type: ResourceType, patch_idx: usize) {
         if self.state != TruckState::ReturningToBase {
             self.target = None;
             self.state = TruckState::Mining;
             self.cargo = Some(resource_type);
+            self.mining_patch = Some(patch_idx);
+            self.salvaging = false;
             self.mining_progress = 0.0;
             self.last_mining_position = Some(self.position);
         }
     }
     
+    /// Apply one enemy hit, softened by armor.
+    pub fn take_damage(&mut self, damage: u32, stats: &TruckStats) {
+        let damage = damage.saturating_sub(stats.armor).max(1);
+        self.health = self.health.saturating_sub(damage);
+    }
+    
     pub fn bounds(&self) -> Rect {
         Rect::from_center_size(self.position, Vec2::splat(self.size))
     }
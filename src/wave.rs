@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use egui::Pos2;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::balance::{ArchetypeSpawns, Balance, SpawnGroup, WaveTable};
+use crate::enemy::{EnemyArchetype, EnemySize};
+
+/// An enemy the director wants placed this tick.
+#[derive(Debug, Clone, Copy)]
+pub struct WaveSpawn {
+    pub position: Pos2,
+    pub size: EnemySize,
+    /// Special kind taking the place of a plain enemy of `size`, if any.
+    pub archetype: Option<EnemyArchetype>,
+    /// A boss rather than a regular enemy.
+    pub boss: bool,
+    /// Scales the enemy's base health for later waves.
+    pub health_multiplier: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct PendingSpawn {
+    size: EnemySize,
+    archetype: Option<EnemyArchetype>,
+    boss: bool,
+    position: Pos2,
+}
+
+/// Paces enemy arrivals: a break, then a wave whose enemies trickle in a
+/// few at a time, then another break.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveDirector {
+    /// Number of the current (or most recently finished) wave; 0 before the first.
+    pub wave_number: u32,
+    /// Seconds until the next wave. Only counts down once the current wave
+    /// has finished spawning.
+    pub countdown: f32,
+    pending: VecDeque<PendingSpawn>,
+    spawn_timer: f32,
+}
+
+impl WaveDirector {
+    pub fn new(grace_period: f32) -> Self {
+        Self {
+            wave_number: 0,
+            countdown: grace_period,
+            pending: VecDeque::new(),
+            spawn_timer: 0.0,
+        }
+    }
+
+    /// True while the current wave still has enemies left to place.
+    pub fn is_spawning(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn remaining_in_wave(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn update(&mut self, delta_time: f32, balance: &Balance, rng: &mut ChaCha8Rng) -> Vec<WaveSpawn> {
+        let mut spawns = Vec::new();
+
+        if self.pending.is_empty() {
+            self.countdown -= delta_time;
+            if self.countdown > 0.0 {
+                return spawns;
+            }
+            self.wave_number += 1;
+            self.start_wave(balance, rng);
+            self.spawn_timer = 0.0;
+        } else {
+            self.spawn_timer -= delta_time;
+        }
+
+        let escalation = &balance.waves.escalation;
+        let health_multiplier = escalation.health_growth.powi(self.wave_number as i32 - 1);
+
+        while self.spawn_timer <= 0.0 {
+            let Some(pending) = self.pending.pop_front() else { break };
+            spawns.push(WaveSpawn {
+                position: pending.position,
+                size: pending.size,
+                archetype: pending.archetype,
+                boss: pending.boss,
+                health_multiplier,
+            });
+            self.spawn_timer += balance.waves.spawn_spacing;
+        }
+
+        if self.pending.is_empty() {
+            self.countdown = balance.waves.break_time;
+        }
+
+        spawns
+    }
+
+    fn start_wave(&mut self, balance: &Balance, rng: &mut ChaCha8Rng) {
+        for group in groups_for_wave(&balance.waves, self.wave_number) {
+            let center = match group.direction {
+                Some(degrees) => degrees.to_radians(),
+                None => rng.gen_range(0.0..std::f32::consts::TAU),
+            };
+            let spread = group.spread.to_radians();
+
+            let sizes = std::iter::repeat_n(EnemySize::Small, group.small as usize)
+                .chain(std::iter::repeat_n(EnemySize::Medium, group.medium as usize))
+                .chain(std::iter::repeat_n(EnemySize::Large, group.large as usize));
+            for size in sizes {
+                let angle = center + rng.gen_range(-spread..=spread);
+                let distance = rng.gen_range(balance.spawn_distance_min..=balance.spawn_distance_max);
+                let position = Pos2::new(angle.cos() * distance, angle.sin() * distance);
+                let archetype = roll_archetype(&balance.waves.archetypes, self.wave_number, rng);
+                self.pending.push_back(PendingSpawn { size, archetype, boss: false, position });
+            }
+        }
+
+        // Every few waves a boss brings up the rear
+        let every = balance.boss.every_waves;
+        if every > 0 && self.wave_number.is_multiple_of(every) {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = balance.spawn_distance_max;
+            let position = Pos2::new(angle.cos() * distance, angle.sin() * distance);
+            self.pending.push_back(PendingSpawn { size: EnemySize::Large, archetype: None, boss: true, position });
+        }
+    }
+}
+
+/// Pick which archetype, if any, takes the place of one wave enemy.
+fn roll_archetype(spawns: &ArchetypeSpawns, wave_number: u32, rng: &mut ChaCha8Rng) -> Option<EnemyArchetype> {
+    let roll = rng.gen_range(0.0..1.0_f32);
+    let mut total = 0.0;
+    for archetype in EnemyArchetype::ALL {
+        let spawn = spawns.get(archetype);
+        if wave_number < spawn.from_wave {
+            continue;
+        }
+        total += spawn.weight;
+        if roll < total {
+            return Some(archetype);
+        }
+    }
+    None
+}
+
+/// Groups for a 1-based wave number. Waves past the authored list repeat
+/// the last one with larger counts and, periodically, an extra group.
+pub fn groups_for_wave(table: &WaveTable, wave_number: u32) -> Vec<SpawnGroup> {
+    let index = wave_number.saturating_sub(1) as usize;
+    if let Some(wave) = table.waves.get(index) {
+        return wave.groups.clone();
+    }
+    let Some(last) = table.waves.last() else { return Vec::new() };
+
+    let extra_waves = (index + 1 - table.waves.len()) as u32;
+    let growth = table.escalation.count_growth.powi(extra_waves as i32);
+    let scale = |count: u32| (count as f32 * growth).round() as u32;
+    let scaled = |group: &SpawnGroup| SpawnGroup {
+        small: scale(group.small),
+        medium: scale(group.medium),
+        large: scale(group.large),
+        ..group.clone()
+    };
+
+    let mut groups: Vec<SpawnGroup> = last.groups.iter().map(scaled).collect();
+    if table.escalation.extra_group_every > 0
+        && let Some(first) = last.groups.first() {
+        for _ in 0..extra_waves / table.escalation.extra_group_every {
+            groups.push(SpawnGroup { direction: None, ..scaled(first) });
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use crate::balance::{ArchetypeSpawn, WaveDefinition};
+
+    fn only(archetype: EnemyArchetype, from_wave: u32) -> ArchetypeSpawns {
+        let none = ArchetypeSpawn { weight: 0.0, from_wave: 1 };
+        let mut spawns = ArchetypeSpawns { scout: none, tank: none, spitter: none, burrower: none, splitter: none };
+        let spawn = ArchetypeSpawn { weight: 1.0, from_wave };
+        match archetype {
+            EnemyArchetype::Scout => spawns.scout = spawn,
+            EnemyArchetype::Tank => spawns.tank = spawn,
+            EnemyArchetype::Spitter => spawns.spitter = spawn,
+            EnemyArchetype::Burrower => spawns.burrower = spawn,
+            EnemyArchetype::Splitter => spawns.splitter = spawn,
+        }
+        spawns
+    }
+
+    #[test]
+    fn roll_archetype_respects_weights_and_first_wave() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let spawns = only(EnemyArchetype::Tank, 4);
+        for _ in 0..100 {
+            assert_eq!(roll_archetype(&spawns, 3, &mut rng), None);
+            assert_eq!(roll_archetype(&spawns, 4, &mut rng), Some(EnemyArchetype::Tank));
+        }
+    }
+
+    #[test]
+    fn roll_archetype_gives_plain_enemies_with_no_weight() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut spawns = only(EnemyArchetype::Scout, 1);
+        spawns.scout.weight = 0.0;
+        assert!((0..100).all(|_| roll_archetype(&spawns, 10, &mut rng).is_none()));
+    }
+
+    fn one_wave_of(small: u32, medium: u32) -> Balance {
+        let mut balance = Balance::default();
+        balance.waves.break_time = 5.0;
+        balance.waves.spawn_spacing = 1.0;
+        balance.waves.waves = vec![WaveDefinition {
+            groups: vec![SpawnGroup { small, medium, direction: Some(0.0), ..SpawnGroup::default() }],
+        }];
+        balance.waves.archetypes = only(EnemyArchetype::Scout, u32::MAX);
+        balance.boss.every_waves = 0;
+        balance
+    }
+
+    #[test]
+    fn waves_wait_for_the_countdown_then_trickle_in() {
+        let balance = one_wave_of(2, 1);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut director = WaveDirector::new(2.0);
+        let mut spawn_times = Vec::new();
+        for step in 1..=8 {
+            let spawns = director.update(0.5, &balance, &mut rng);
+            spawn_times.extend(spawns.iter().map(|_| step as f32 * 0.5));
+        }
+
+        assert_eq!(spawn_times, vec![2.0, 3.0, 4.0]);
+        assert_eq!(director.wave_number, 1);
+        assert!(!director.is_spawning());
+        assert_eq!(director.countdown, balance.waves.break_time);
+    }
+
+    #[test]
+    fn waves_past_the_list_grow() {
+        let mut balance = one_wave_of(10, 5);
+        balance.waves.escalation.count_growth = 1.5;
+        balance.waves.escalation.extra_group_every = 2;
+
+        let counts = |wave| -> Vec<(u32, u32)> {
+            groups_for_wave(&balance.waves, wave).iter().map(|g| (g.small, g.medium)).collect()
+        };
+        assert_eq!(counts(1), vec![(10, 5)]);
+        assert_eq!(counts(2), vec![(15, 8)]);
+        assert_eq!(counts(3), vec![(23, 11), (23, 11)]);
+    }
+
+    #[test]
+    fn later_waves_are_tougher_and_bring_bosses() {
+        let mut balance = one_wave_of(1, 0);
+        balance.waves.escalation.health_growth = 2.0;
+        balance.boss.every_waves = 2;
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut director = WaveDirector::new(0.0);
+
+        let mut waves = Vec::new();
+        while director.wave_number < 3 || director.is_spawning() {
+            for spawn in director.update(0.5, &balance, &mut rng) {
+                waves.push((director.wave_number, spawn.boss, spawn.health_multiplier));
+            }
+        }
+        assert_eq!(waves, vec![
+            (1, false, 1.0),
+            (2, false, 2.0),
+            (2, true, 2.0),
+            (3, false, 4.0),
+        ]);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Gun a truck can carry. Stats and recipes live in `Balance::weapons`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WeaponType {
+    /// Quick, cheap single shots; made for swarms of small enemies.
+    MachineGun,
+    /// Slow shells that burst over an area where they land.
+    Cannon,
+    /// Long reach and heavy hits, but a slow rate of fire.
+    Rifle,
+    /// Short-range cone that burns everything in front of it at once.
+    Flamethrower,
+}
+
+/// What a weapon fires. Factories make it in boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AmmoType {
+    Bullets,
+    Shells,
+    Fuel,
+}
+
+impl WeaponType {
+    pub const ALL: [WeaponType; 4] = [
+        WeaponType::MachineGun,
+        WeaponType::Cannon,
+        WeaponType::Rifle,
+        WeaponType::Flamethrower,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeaponType::MachineGun => "Machine Gun",
+            WeaponType::Cannon => "Cannon",
+            WeaponType::Rifle => "Rifle",
+            WeaponType::Flamethrower => "Flamethrower",
+        }
+    }
+
+    /// Two-letter tag for truck labels.
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            WeaponType::MachineGun => "MG",
+            WeaponType::Cannon => "CN",
+            WeaponType::Rifle => "RF",
+            WeaponType::Flamethrower => "FL",
+        }
+    }
+}
+
+impl AmmoType {
+    pub const ALL: [AmmoType; 3] = [AmmoType::Bullets, AmmoType::Shells, AmmoType::Fuel];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AmmoType::Bullets => "Bullets",
+            AmmoType::Shells => "Shells",
+            AmmoType::Fuel => "Fuel",
+        }
+    }
+}